@@ -1,3 +1,88 @@
+//! Connection with neovim shared by page and nv, which might be
+//! used by other tools that want to display something in neovim.
+//! Neovim API is available through `nvim_actions`, which might be
+//! any type constructed from `Neovim<IoWrite>`, e.g. itself:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), connection::Error> {
+//! use connection::{ConnectionOptions, ListenAddressPassing, Neovim, IoWrite};
+//!
+//! let conn_opt = ConnectionOptions {
+//!     address: &std::env::var("NVIM").ok(),
+//!     token: &None,
+//!     nvim_bin: "nvim",
+//!     nvim_env: &[],
+//!     config: &None,
+//!     custom_args: &None,
+//!     socket: &None,
+//!     listen_addr_passing: ListenAddressPassing::Argument,
+//!     connect_interval: std::time::Duration::from_millis(16),
+//!     connect_timeout: std::time::Duration::from_millis(4096),
+//!     ready_handshake: false,
+//!     notification_capacity: 16,
+//!     print_protection: false,
+//!     follow: false,
+//!     heartbeat_interval: None,
+//!     remove_tmp_dir: false,
+//!     reconnect: false,
+//! };
+//!
+//! let mut nvim_conn = connection::open::<Neovim<IoWrite>>(
+//!     &std::env::temp_dir(),
+//!     std::process::id().into(),
+//!     conn_opt
+//! ).await?;
+//!
+//! nvim_conn.nvim_actions
+//!     .command("echo 'output'")
+//!     .await
+//!     .expect("Cannot write output");
+//!
+//! connection::close_and_exit(&mut nvim_conn).await
+//! # }
+//! ```
+//!
+//! With `pager` feature page's own `NeovimActions` (output buffers,
+//! instances and sinks that write into them) might be used as
+//! `nvim_actions` instead, configured by types from `cli` module:
+//!
+//! ```no_run
+//! # #[cfg(feature = "pager")]
+//! # async fn example(conn_opt: connection::ConnectionOptions<'_>) -> Result<(), connection::Error> {
+//! use connection::{NeovimActions, cli::OutputBackend};
+//! use std::io::Write;
+//!
+//! let mut nvim_conn = connection::open::<NeovimActions>(
+//!     &std::env::temp_dir(),
+//!     std::process::id().into(),
+//!     conn_opt
+//! ).await?;
+//!
+//! let outp = nvim_conn.nvim_actions
+//!     .create_replacing_output_buffer(OutputBackend::Buffer)
+//!     .await;
+//! if let connection::neovim::SinkTarget::Buffer(buf_nr) = outp.sink {
+//!     let mut sink = nvim_conn.nvim_actions.open_buffer_sink(buf_nr, false, false, false, None);
+//!     writeln!(sink, "output").expect("Cannot write output");
+//! }
+//!
+//! connection::close_and_exit(&mut nvim_conn).await
+//! # }
+//! ```
+
+#[cfg(feature = "pager")]
+#[path = "pager/cli.rs"]
+pub mod cli;
+#[cfg(feature = "pager")]
+#[path = "pager/neovim.rs"]
+pub mod neovim;
+#[cfg(feature = "pager")]
+#[path = "pager/ansi.rs"]
+pub mod ansi;
+
+#[cfg(feature = "pager")]
+pub use neovim::Actions as NeovimActions;
+
 pub use crate::{
     io_handler::{
         PipeOrSocketHandler,
@@ -102,23 +187,210 @@ pub fn init_panic_hook() {
             }
         }
 
+        release_socket_lock();
+
         default_panic_hook(panic_info);
     }));
 }
 
 
+/// Lock file that's held while child neovim socket is being created
+static SOCKET_LOCK: std::sync::Mutex<Option<std::path::PathBuf>> = std::sync::Mutex::new(None);
+
+/// Acquires lock file next to socket path, so concurrent
+/// invocations with the same socket won't spawn two neovim
+/// processes on it. Waits while lock is held by another
+/// invocation, and takes it over if that's too long
+async fn acquire_socket_lock(socket_path: &Path) -> Result<(), Error> {
+    let socket_name = socket_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let lock_path = socket_path
+        .with_file_name(format!("{socket_name}.lock"));
+
+    let mut i = 0;
+    loop {
+        let lock = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path);
+
+        match lock {
+            Ok(_) => break,
+
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                if i == 256 {
+                    log::warn!(target: "socket lock", "Taking over stale lock {lock_path:?}");
+
+                    break
+                }
+
+                tokio::time::sleep(std::time::Duration::from_millis(16)).await;
+
+                i += 1;
+            }
+
+            Err(e) => return Err(Error::Prepare(format!("cannot create socket lock {lock_path:?}: {e}"))),
+        }
+    }
+
+    log::trace!(target: "socket lock", "acquired {lock_path:?}, attempts={i}");
+
+    SOCKET_LOCK
+        .lock()
+        .expect("Socket lock poisoned")
+        .replace(lock_path);
+
+    Ok(())
+}
+
+/// Removes lock file acquired for socket if it's still held
+pub fn release_socket_lock() {
+    let Ok(mut lock) = SOCKET_LOCK.lock() else {
+        return
+    };
+
+    if let Some(lock_path) = lock.take() {
+        log::trace!(target: "socket lock", "released {lock_path:?}");
+
+        if let Err(e) = std::fs::remove_file(&lock_path) {
+            log::error!(target: "socket lock", "Cannot remove {lock_path:?}: {e}");
+        }
+    }
+}
+
+
 /// This struct contains all neovim-related data which is
 /// required by page after connection with neovim is established
 pub struct NeovimConnection<Apis: From<Neovim<IoWrite>>> {
     pub nvim_proc: Option<tokio::task::JoinHandle<Result<ExitStatus, std::io::Error>>>,
     pub nvim_actions: Apis,
     pub initial_buf_number: i64,
+    pub initial_win_id: i64,
     pub channel: u64,
+    pub address: String,
     pub initial_win_and_buf: (Window<IoWrite>, Buffer<IoWrite>),
     pub rx: tokio::sync::mpsc::Receiver<NotificationFromNeovim>,
     handle: tokio::task::JoinHandle<Result<(), Box<nvim_rs::error::LoopError>>>,
+    heartbeat: Option<tokio::task::JoinHandle<()>>,
+    tmp_dir: Option<std::path::PathBuf>,
+    pub reconnect: Option<Reconnect>,
+    pub connection_lost: ConnectionLost,
+}
+
+
+/// Signal from heartbeat that neovim doesn't respond
+/// and cannot be reconnected, so page should exit
+pub struct ConnectionLost(Option<tokio::sync::oneshot::Receiver<Error>>);
+
+impl ConnectionLost {
+    /// Resolves with the reason once connection is lost,
+    /// never resolves without --heartbeat-ms
+    pub async fn wait(&mut self) -> Error {
+        if let Some(lost) = &mut self.0 {
+            let lost = lost.await;
+            self.0 = None;
+
+            if let Ok(e) = lost {
+                return e
+            }
+        }
+
+        std::future::pending().await
+    }
+}
+
+/// Options that determine how connection with neovim is established
+pub struct ConnectionOptions<'a> {
+    /// Address of running neovim, child neovim is spawned if it's missing
+    pub address: &'a Option<String>,
+    /// Token that's checked by running neovim before any other request
+    pub token: &'a Option<String>,
+    /// Binary of child neovim
+    pub nvim_bin: &'a str,
+    /// Environment variables set for child neovim in addition to inherited
+    pub nvim_env: &'a [(String, String)],
+    /// Config of child neovim
+    pub config: &'a Option<String>,
+    /// Arguments of child neovim
+    pub custom_args: &'a Option<String>,
+    /// Path of socket listened by child neovim
+    pub socket: &'a Option<String>,
+    /// How child neovim is told which address it should listen
+    pub listen_addr_passing: ListenAddressPassing,
+    /// Interval between attempts to connect to child neovim
+    pub connect_interval: std::time::Duration,
+    /// How long to wait until child neovim serves requests
+    pub connect_timeout: std::time::Duration,
+    /// Whether child neovim notifies when it's ready instead of being polled
+    pub ready_handshake: bool,
+    /// How many notifications from neovim might wait to be processed
+    pub notification_capacity: usize,
+    /// Whether to print protection from shell redirection (see --help[-W])
+    pub print_protection: bool,
+    /// Whether cursor follows output (reported on `page_get_status` request)
+    pub follow: bool,
+    /// Interval between checks that neovim at address is still alive,
+    /// page exits once it isn't [not checked if missing]
+    pub heartbeat_interval: Option<std::time::Duration>,
+    /// Whether temporary directory belongs to this invocation only
+    /// and should be removed on exit
+    pub remove_tmp_dir: bool,
+    /// Whether `reconnect` is provided to connect again
+    /// to the same address once connection is lost
+    pub reconnect: bool,
+}
+
+
+/// Errors that prevent connection with neovim from being established
+#[derive(Debug)]
+pub enum Error {
+    /// Neovim isn't reachable at address
+    Connect { address: String, reason: String },
+    /// Child neovim process cannot be spawned
+    Spawn(std::io::Error),
+    /// Child neovim process isn't reachable or ready in time
+    Timeout { address: String, reason: String },
+    /// Socket or directories required by child neovim cannot be prepared
+    Prepare(String),
+    /// Neovim rejected token or cannot check it
+    Authenticate(String),
+    /// Neovim didn't respond properly to initial requests
+    Handshake(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Connect { address, reason } => write!(f, "Cannot connect to neovim at {address}: {reason}"),
+            Error::Spawn(e) => write!(f, "Cannot spawn a child neovim process: {e}"),
+            Error::Timeout { address, reason } => write!(f, "Timed out connecting to child neovim at {address}: {reason}"),
+            Error::Prepare(reason) => write!(f, "Cannot prepare child neovim: {reason}"),
+            Error::Authenticate(reason) => write!(f, "Cannot authenticate: {reason}"),
+            Error::Handshake(reason) => write!(f, "Cannot query initial state: {reason}"),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Exit code distinct for each failure that scripts
+    /// might want to handle: 2 when neovim isn't reachable
+    /// at address, 3 when child neovim cannot be spawned,
+    /// 4 when child neovim isn't ready in time, 1 otherwise
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Connect { .. } => 2,
+            Error::Spawn(_) => 3,
+            Error::Timeout { .. } => 4,
+            Error::Prepare(_) | Error::Authenticate(_) | Error::Handshake(_) => 1,
+        }
+    }
+}
+
+
 /// Connects to parent neovim session or spawns
 /// a new neovim process and connects to it through socket.
 /// Replacement for `nvim_rs::Session::new_child()`,
@@ -126,99 +398,395 @@ pub struct NeovimConnection<Apis: From<Neovim<IoWrite>>> {
 pub async fn open<Apis: From<Neovim<IoWrite>>>(
     tmp_dir: &Path,
     page_id: u128,
-    nvim_listen_addr: &Option<String>,
-    config_path: &Option<String>,
-    custom_nvim_args: &Option<String>,
-    print_protection: bool,
-) -> NeovimConnection<Apis> {
-
-    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    conn_opt: ConnectionOptions<'_>,
+) -> Result<NeovimConnection<Apis>, Error> {
+    let (tx, rx) = tokio::sync::mpsc::channel(conn_opt.notification_capacity.max(1));
 
     let handler = PipeOrSocketHandler {
         page_id: page_id.to_string(),
+        follow: conn_opt.follow,
         tx
     };
 
     let mut nvim_proc = None;
 
-    let (nvim, handle) = match nvim_listen_addr.as_deref() {
-        Some(nvim_listen_addr)
-            if nvim_listen_addr.parse::<std::net::SocketAddr>()
-                .is_ok() =>
-        {
-            let tcp = tokio::net::TcpStream::connect(nvim_listen_addr)
-                .await
-                .expect("Cannot connect to neovim at TCP/IP address");
+    let mut address = conn_opt.address
+        .clone()
+        .unwrap_or_default();
 
-            let (rx, tx) = tokio::io::split(tcp);
-            let (rx, tx) = (IoRead::Tcp(rx.compat()), IoWrite::Tcp(tx.compat_write()));
-            let (nvim, io) = Neovim::<IoWrite>::new(rx, tx, handler);
-            let io_handle = tokio::task::spawn(io);
+    let tcp_address = match conn_opt.address.as_deref() {
+        Some(nvim_listen_addr) => is_tcp_address(nvim_listen_addr).await,
+        None => false,
+    };
 
-            (nvim, io_handle)
-        }
+    let reconnect_handler = conn_opt.reconnect.then(|| handler.clone());
 
+    let (nvim, handle) = match conn_opt.address.as_deref() {
         Some(nvim_listen_addr) => {
-            let ipc = parity_tokio_ipc::Endpoint::connect(nvim_listen_addr)
-                .await
-                .expect("Cannot connect to neovim at path");
-
-            let (rx, tx) = tokio::io::split(ipc);
-            let (rx, tx) = (IoRead::Ipc(rx.compat()), IoWrite::Ipc(tx.compat_write()));
-            let (nvim, io) = Neovim::<IoWrite>::new(rx, tx, handler);
-            let io_handle = tokio::task::spawn(io);
-
-            (nvim, io_handle)
+            connect_to_address(nvim_listen_addr, tcp_address, handler)
+                .await?
         }
 
         None => {
-            let (nvim, io_handle, child) = create_new_neovim_process_ipc(
+            let socket_path = determine_child_socket_path(tmp_dir, page_id, conn_opt.socket)?;
+            address = socket_path
+                .to_string_lossy()
+                .to_string();
+
+            acquire_socket_lock(&socket_path)
+                .await?;
+
+            let session = attach_or_spawn_child_neovim(
                 tmp_dir,
-                page_id,
-                config_path,
-                custom_nvim_args,
-                print_protection,
+                &socket_path,
+                &conn_opt,
                 handler
             )
             .await;
-            nvim_proc = Some(child);
+
+            // Socket is live now, so other invocations can attach to it
+            release_socket_lock();
+
+            let (nvim, io_handle, child) = session?;
+            nvim_proc = child;
 
             (nvim, io_handle)
         }
     };
 
-    let channel = nvim
-        .get_api_info()
-        .await
-        .expect("No API info")
-        .get(0)
-        .expect("No channel")
-        .as_u64()
-        .expect("Channel not a number");
-
-    let initial_win = nvim
-        .get_current_win()
-        .await
-        .expect("Cannot get initial window");
+    if let (Some(token), None) = (conn_opt.token, &nvim_proc) {
+        authenticate(&nvim, token).await?;
+    }
 
-    let initial_buf = nvim
-        .get_current_buf()
+    // Initial state is queried in a single round-trip
+    let calls = vec![
+        Value::from(vec![Value::from("nvim_get_api_info"), Value::from(Vec::<Value>::new())]),
+        Value::from(vec![Value::from("nvim_get_current_win"), Value::from(Vec::<Value>::new())]),
+        Value::from(vec![Value::from("nvim_get_current_buf"), Value::from(Vec::<Value>::new())]),
+        Value::from(vec![
+            Value::from("nvim_call_function"),
+            Value::from(vec![Value::from("win_getid"), Value::from(Vec::<Value>::new())])
+        ]),
+        Value::from(vec![
+            Value::from("nvim_call_function"),
+            Value::from(vec![Value::from("bufnr"), Value::from(Vec::<Value>::new())])
+        ]),
+    ];
+
+    let atomic_response = nvim
+        .call_atomic(calls)
         .await
-        .expect("Cannot get initial buffer");
+        .map_err(|e| Error::Handshake(e.to_string()))?;
 
-    let initial_buf_number = initial_buf
-        .get_number()
-        .await
-        .expect("Cannot get initial buffer number");
+    if let Some(err) = atomic_response.get(1).filter(|e| !e.is_nil()) {
+        return Err(Error::Handshake(err.to_string()))
+    }
+
+    let unexpected = |what: &str| Error::Handshake(format!("unexpected {what}"));
+
+    let [api_info, initial_win, initial_buf, initial_win_id, initial_buf_number] = atomic_response
+        .first()
+        .and_then(Value::as_array)
+        .and_then(|results| <&[Value; 5]>::try_from(results.as_slice()).ok())
+        .ok_or_else(|| unexpected("response"))?
+        .clone();
+
+    let channel = api_info
+        .as_array()
+        .and_then(|info| info.first())
+        .and_then(Value::as_u64)
+        .ok_or_else(|| unexpected("channel"))?;
+
+    let initial_win = Window::new(initial_win, nvim.clone());
+    let initial_buf = Buffer::new(initial_buf, nvim.clone());
+
+    let initial_win_id = initial_win_id
+        .as_i64()
+        .ok_or_else(|| unexpected("window id"))?;
+
+    let initial_buf_number = initial_buf_number
+        .as_i64()
+        .ok_or_else(|| unexpected("buffer number"))?;
+
+    // Child neovim spawned by page isn't expected to be replaced
+    let reconnect = reconnect_handler.filter(|_| nvim_proc.is_none()).map(|handler| Reconnect {
+        address: address.clone(),
+        tcp_address,
+        handler,
+        token: conn_opt.token.clone(),
+        connect_interval: conn_opt.connect_interval,
+        connect_timeout: conn_opt.connect_timeout,
+    });
+
+    // Child neovim is awaited on exit, so only parent neovim is checked
+    let (heartbeat, connection_lost) = match (conn_opt.heartbeat_interval, &nvim_proc) {
+        (Some(interval), None) => {
+            let (lost_tx, lost_rx) = tokio::sync::oneshot::channel();
+            let heartbeat = spawn_heartbeat(nvim.clone(), address.clone(), interval, reconnect.clone(), lost_tx);
+
+            (Some(heartbeat), ConnectionLost(Some(lost_rx)))
+        }
+        _ => (None, ConnectionLost(None)),
+    };
 
-    NeovimConnection {
+    Ok(NeovimConnection {
         nvim_proc,
         nvim_actions: From::from(nvim),
         initial_buf_number,
+        initial_win_id,
         channel,
+        address,
         initial_win_and_buf: (initial_win, initial_buf),
         rx,
-        handle
+        handle,
+        heartbeat,
+        tmp_dir: conn_opt.remove_tmp_dir.then(|| tmp_dir.to_path_buf()),
+        reconnect,
+        connection_lost,
+    })
+}
+
+
+/// Connects to neovim at address over TCP/IP or IPC
+async fn connect_to_address(
+    nvim_listen_addr: &str,
+    tcp_address: bool,
+    handler: PipeOrSocketHandler
+) -> Result<(
+    Neovim<IoWrite>,
+    tokio::task::JoinHandle<Result<(), Box<nvim_rs::error::LoopError>>>
+), Error> {
+    let connect_error = |e: std::io::Error| Error::Connect {
+        address: nvim_listen_addr.to_string(),
+        reason: e.to_string()
+    };
+
+    if tcp_address {
+        let tcp = tokio::net::TcpStream::connect(nvim_listen_addr)
+            .await
+            .map_err(connect_error)?;
+
+        let (rx, tx) = tokio::io::split(tcp);
+        let (rx, tx) = (IoRead::Tcp(rx.compat()), IoWrite::Tcp(tx.compat_write()));
+        let (nvim, io) = Neovim::<IoWrite>::new(rx, tx, handler);
+        let io_handle = tokio::task::spawn(io);
+
+        return Ok((nvim, io_handle))
+    }
+
+    let ipc = parity_tokio_ipc::Endpoint::connect(nvim_listen_addr)
+        .await
+        .map_err(connect_error)?;
+
+    Ok(create_ipc_session(ipc, handler))
+}
+
+
+/// Connects again to neovim which connection was lost, e.g. when
+/// its socket was replaced. Notifications from the new connection
+/// are received through the same `NeovimConnection::rx`
+#[derive(Clone)]
+pub struct Reconnect {
+    address: String,
+    tcp_address: bool,
+    handler: PipeOrSocketHandler,
+    token: Option<String>,
+    connect_interval: std::time::Duration,
+    connect_timeout: std::time::Duration,
+}
+
+impl Reconnect {
+    /// Attempts to connect with doubling intervals until timeout is
+    /// exceeded. Returns connection with its channel id, which neovim
+    /// should use to send notifications from now on
+    pub async fn connect(&self) -> Result<(Neovim<IoWrite>, u64), Error> {
+        let started = std::time::Instant::now();
+        let mut interval = self.connect_interval;
+
+        loop {
+            log::trace!(target: "reconnect", "{}", self.address);
+
+            let connected = connect_to_address(&self.address, self.tcp_address, self.handler.clone())
+                .await;
+
+            let e = match connected {
+                Ok((nvim, _)) => {
+                    if let Some(token) = &self.token {
+                        authenticate(&nvim, token).await?;
+                    }
+
+                    let (channel, _) = nvim
+                        .get_api_info()
+                        .await
+                        .map_err(|e| Error::Handshake(e.to_string()))
+                        .and_then(|info| info
+                            .first()
+                            .and_then(Value::as_u64)
+                            .map(|channel| (channel, info))
+                            .ok_or_else(|| Error::Handshake(String::from("unexpected channel")))
+                        )?;
+
+                    return Ok((nvim, channel))
+                }
+                Err(e) => e,
+            };
+
+            if started.elapsed() + interval > self.connect_timeout {
+                return Err(e)
+            }
+
+            tokio::time::sleep(interval).await;
+            interval *= 2;
+        }
+    }
+}
+
+
+/// Periodically issues cheap request to neovim that must be answered
+/// within interval. Once it isn't, neovim is reconnected with --reconnect
+/// or page is signaled through `lost` to exit
+fn spawn_heartbeat(
+    nvim: Neovim<IoWrite>,
+    address: String,
+    interval: std::time::Duration,
+    reconnect: Option<Reconnect>,
+    lost: tokio::sync::oneshot::Sender<Error>
+) -> tokio::task::JoinHandle<()> {
+    log::trace!(target: "heartbeat", "every {interval:?}");
+
+    tokio::task::spawn(async move {
+        let mut nvim = nvim;
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticks.tick().await;
+
+            let reason = match tokio::time::timeout(interval, nvim.get_mode()).await {
+                Ok(Ok(_)) => continue,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => format!("no response in {interval:?}"),
+            };
+            log::error!(target: "heartbeat", "Neovim doesn't respond: {reason}");
+
+            if let Some(reconnect) = &reconnect {
+                match tokio::time::timeout(reconnect.connect_timeout, reconnect.connect()).await {
+                    Ok(Ok((reconnected, _))) => {
+                        log::info!(target: "heartbeat", "Reconnected to {address}");
+
+                        nvim = reconnected;
+                        continue
+                    }
+                    Ok(Err(e)) => log::error!(target: "heartbeat", "{e}"),
+                    Err(_) => log::error!(target: "heartbeat", "Reconnect timed out"),
+                }
+            }
+
+            let _ = lost.send(Error::Connect {
+                address,
+                reason: format!("neovim doesn't respond: {reason}")
+            });
+
+            return
+        }
+    })
+}
+
+
+/// Checks whether address is `host:port` that should be connected
+/// through TCP/IP: hostnames are resolved and anything that looks
+/// like path to UNIX socket or named pipe is never resolved
+async fn is_tcp_address(nvim_listen_addr: &str) -> bool {
+    if nvim_listen_addr.contains(['/', '\\']) {
+        return false
+    }
+
+    if nvim_listen_addr.parse::<std::net::SocketAddr>().is_ok() {
+        return true
+    }
+
+    match tokio::net::lookup_host(nvim_listen_addr).await {
+        Ok(mut resolved) => resolved.next().is_some(),
+        Err(e) => {
+            log::trace!(target: "address", "{nvim_listen_addr} isn't resolved: {e}");
+            false
+        }
+    }
+}
+
+
+/// Attaches to neovim from prior invocation that might already
+/// listen on socket, otherwise spawns a new one. Child process
+/// is returned only when it's spawned by this invocation
+async fn attach_or_spawn_child_neovim(
+    tmp_dir: &Path,
+    socket_path: &Path,
+    conn_opt: &ConnectionOptions<'_>,
+    handler: PipeOrSocketHandler
+) -> Result<(
+    Neovim<IoWrite>,
+    tokio::task::JoinHandle<Result<(), Box<nvim_rs::error::LoopError>>>,
+    Option<tokio::task::JoinHandle<Result<ExitStatus, std::io::Error>>>
+), Error> {
+    let live_ipc = if socket_path.exists() {
+        parity_tokio_ipc::Endpoint::connect(socket_path)
+            .await
+            .ok()
+    } else {
+        None
+    };
+
+    if let Some(ipc) = live_ipc {
+        log::info!(target: "socket", "Attached to neovim at {socket_path:?}");
+
+        let (nvim, io_handle) = create_ipc_session(ipc, handler);
+
+        return Ok((nvim, io_handle, None))
+    }
+
+    if socket_path.exists() {
+        log::info!(target: "socket", "Removing stale socket {socket_path:?}");
+
+        std::fs::remove_file(socket_path)
+            .map_err(|e| Error::Prepare(format!("cannot remove stale socket {socket_path:?}: {e}")))?;
+    }
+
+    log::info!(target: "socket", "Spawning neovim at {socket_path:?}");
+
+    let (nvim, io_handle, child) = create_new_neovim_process_ipc(
+        tmp_dir,
+        socket_path,
+        conn_opt,
+        handler
+    )
+    .await?;
+
+    Ok((nvim, io_handle, Some(child)))
+}
+
+
+/// Sends token to `PageAuthenticate` function which should be
+/// defined in neovim config and return true for valid tokens.
+/// Fails before any other request otherwise
+async fn authenticate(nvim: &Neovim<IoWrite>, token: &str) -> Result<(), Error> {
+    let authenticated = nvim
+        .call_function("PageAuthenticate", vec![Value::from(token)])
+        .await;
+
+    match authenticated {
+        Ok(v) if v.as_bool() == Some(true) || v.as_i64() == Some(1) => {
+            log::trace!(target: "authenticate", "token accepted");
+
+            Ok(())
+        },
+
+        Ok(v) => Err(Error::Authenticate(format!(
+            "neovim rejected token, PageAuthenticate returned {v}"
+        ))),
+
+        Err(e) => Err(Error::Authenticate(format!(
+            "PageAuthenticate(token) should be defined in neovim config: {e}"
+        ))),
     }
 }
 
@@ -228,15 +796,55 @@ pub async fn open<Apis: From<Neovim<IoWrite>>>(
 pub async fn close_and_exit<Apis: From<Neovim<IoWrite>>>(
     nvim_connection: &mut NeovimConnection<Apis>
 ) -> ! {
+    let exit_code = close(nvim_connection)
+        .await;
+
+    std::process::exit(exit_code)
+}
+
+
+/// Closes connection like `close_and_exit` after connection
+/// was lost, then reports error and exits with its code
+pub async fn close_and_exit_with_error<Apis: From<Neovim<IoWrite>>>(
+    nvim_connection: &mut NeovimConnection<Apis>,
+    e: &Error
+) -> ! {
+    close(nvim_connection)
+        .await;
+
+    eprintln!("error: {e}");
+
+    std::process::exit(e.exit_code())
+}
+
+
+/// Waits for child neovim, removes temporary files and stops
+/// background tasks. Returns exit code of child neovim
+async fn close<Apis: From<Neovim<IoWrite>>>(
+    nvim_connection: &mut NeovimConnection<Apis>
+) -> i32 {
     log::trace!(target: "exit", "close and exit");
 
+    release_socket_lock();
+
+    let mut exit_code = 0;
+
     if let Some(ref mut process) = nvim_connection.nvim_proc {
-        if !process.is_finished() {
-            process
-               .await
-               .expect("Neovim process was spawned with error")
-               .expect("Neovim process died unexpectedly");
-        }
+        // Finished process still reports its exit status
+        let exit_status = process.await;
+
+        // Neovim removes its socket on exit unless it crashed
+        remove_child_socket(Path::new(&nvim_connection.address));
+
+        exit_code = child_neovim_exit_code(exit_status);
+    }
+
+    if let Some(ref tmp_dir) = nvim_connection.tmp_dir {
+        remove_tmp_dir(tmp_dir);
+    }
+
+    if let Some(ref heartbeat) = nvim_connection.heartbeat {
+        heartbeat.abort();
     }
 
     nvim_connection.handle
@@ -245,48 +853,167 @@ pub async fn close_and_exit<Apis: From<Neovim<IoWrite>>>(
     log::logger()
         .flush();
 
-    std::process::exit(0)
+    exit_code
+}
+
+
+/// Translates how child neovim process finished into page's exit code:
+/// neovim's own exit code is propagated, termination by signal
+/// is reported as 128 + signal number like shells do
+fn child_neovim_exit_code(
+    exit_status: Result<Result<ExitStatus, std::io::Error>, tokio::task::JoinError>
+) -> i32 {
+    let status = match exit_status {
+        Ok(Ok(status)) => status,
+
+        // Child might be already reaped, there's nothing to report then
+        Ok(Err(e)) => {
+            log::warn!(target: "exit", "Cannot wait for neovim process: {e}");
+
+            return 0
+        },
+        Err(e) => {
+            log::error!(target: "exit", "Neovim process task failed: {e}");
+            eprintln!("error: neovim process task failed: {e}");
+
+            return 1
+        },
+    };
+
+    log::trace!(target: "exit", "Neovim process finished: {status}");
+
+    if status.success() {
+        return 0
+    }
+
+    eprintln!("error: neovim process {status}");
+
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal
+    }
+
+    status.code()
+        .unwrap_or(1)
+}
+
+
+/// Removes socket of child neovim process spawned by page.
+/// Sockets of neovim that page only connected to are kept
+fn remove_child_socket(socket_path: &Path) {
+    match std::fs::remove_file(socket_path) {
+        Ok(_) => log::trace!(target: "socket", "removed {socket_path:?}"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => log::error!(target: "socket", "Cannot remove {socket_path:?}: {e}"),
+    }
+}
+
+
+/// Removes temporary directory of this invocation
+/// with socket and redirection protection inside
+fn remove_tmp_dir(tmp_dir: &Path) {
+    match std::fs::remove_dir_all(tmp_dir) {
+        Ok(_) => log::trace!(target: "tmp dir", "removed {tmp_dir:?}"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => log::error!(target: "tmp dir", "Cannot remove {tmp_dir:?}: {e}"),
+    }
+}
+
+
+/// Prints error to stderr instead of panicking with backtrace
+/// and exits with non-zero code
+pub fn exit_with_error(e: &Error) -> ! {
+    release_socket_lock();
+
+    log::logger()
+        .flush();
+
+    eprintln!("error: {e}");
+
+    std::process::exit(e.exit_code())
 }
 
 
 /// Creates a new session using UNIX socket.
 /// Also prints protection from shell redirection
-/// that could cause some harm (see --help[-W])
+/// that could cause some harm (see --help[-W]).
+/// Fails if child neovim cannot be spawned or isn't reachable
 async fn create_new_neovim_process_ipc(
     tmp_dir: &Path,
-    page_id: u128,
-    config: &Option<String>,
-    custom_args: &Option<String>,
-    print_protection: bool,
+    nvim_listen_addr: &Path,
+    conn_opt: &ConnectionOptions<'_>,
     handler: PipeOrSocketHandler
-) -> (
+) -> Result<(
     Neovim<IoWrite>,
     tokio::task::JoinHandle<Result<(), Box<nvim_rs::error::LoopError>>>,
     tokio::task::JoinHandle<Result<ExitStatus, std::io::Error>>
-) {
-    if print_protection {
-        print_redirect_protection(tmp_dir);
+), Error> {
+    if conn_opt.print_protection {
+        print_redirect_protection(tmp_dir)?;
     }
 
-    let nvim_listen_addr = tmp_dir
-        .join(&format!("socket-{page_id}"));
+    let nvim_listen_addr = nvim_listen_addr.to_path_buf();
+    let listen_addr_passing = conn_opt.listen_addr_passing;
+
+    // Child neovim connects to this endpoint when it's ready
+    let ready_addr = format!("{}-ready", nvim_listen_addr.display());
+    let ready_incoming = conn_opt.ready_handshake
+        .then(|| parity_tokio_ipc::Endpoint::new(ready_addr.clone()).incoming())
+        .and_then(|incoming| incoming
+            .map_err(|e| log::warn!(target: "child neovim handshake", "{e}, polling instead"))
+            .ok()
+        );
 
     let mut nvim_proc = tokio::task::spawn({
-        let (config, custom_args, nvim_listen_addr) = (
-            config.clone(),
-            custom_args.clone(),
-            nvim_listen_addr.clone()
+        let (nvim_bin, nvim_env, config, custom_args, nvim_listen_addr, ready_addr) = (
+            conn_opt.nvim_bin.to_string(),
+            conn_opt.nvim_env.to_vec(),
+            conn_opt.config.clone(),
+            conn_opt.custom_args.clone(),
+            nvim_listen_addr.clone(),
+            ready_incoming.is_some().then_some(ready_addr)
         );
         async move {
             spawn_child_nvim_process(
+                &nvim_bin,
+                &nvim_env,
                 &config,
                 &custom_args,
-                &nvim_listen_addr
+                &nvim_listen_addr,
+                listen_addr_passing,
+                ready_addr.as_deref()
             )
         }
     });
 
-    tokio::time::sleep(std::time::Duration::from_millis(128)).await;
+    let started = std::time::Instant::now();
+
+    let max_attempts = conn_opt.connect_timeout.as_millis() /
+        conn_opt.connect_interval.as_millis().max(1);
+
+    let ready = match ready_incoming {
+        Some(incoming) => {
+            use futures::StreamExt;
+            futures::pin_mut!(incoming);
+
+            let mut ready = false;
+            while !nvim_proc.is_finished() && started.elapsed() < conn_opt.connect_timeout {
+                let accepted = tokio::time::timeout(conn_opt.connect_interval, incoming.next()).await;
+                if let Ok(accepted) = accepted {
+                    ready = matches!(accepted, Some(Ok(_)));
+                    break
+                }
+            }
+
+            log::trace!(target: "child neovim handshake", "ready={ready} after {:?}", started.elapsed());
+            ready
+        },
+        None => false,
+    };
+
+    if !ready {
+        tokio::time::sleep(std::time::Duration::from_millis(128)).await;
+    }
 
     let mut i = 0;
     let e = loop {
@@ -296,16 +1023,44 @@ async fn create_new_neovim_process_ipc(
             Ok(ipc) => {
                 log::trace!(target: "child neovim spawned", "attempts={i}");
 
-                let (rx, tx) = tokio::io::split(ipc);
-                let (rx, tx) = (IoRead::Ipc(rx.compat()), IoWrite::Ipc(tx.compat_write()));
-                let (neovim, io) = Neovim::<IoWrite>::new(rx, tx, handler);
-                let io_handle = tokio::task::spawn(io);
+                let (neovim, io_handle) = create_ipc_session(ipc, handler);
+
+                // Socket might exist before neovim is able to serve requests
+                while i < max_attempts {
+                    let api_info = tokio::time::timeout(
+                        conn_opt.connect_interval,
+                        neovim.get_api_info()
+                    )
+                    .await;
+
+                    match api_info {
+                        Ok(Ok(_)) => {
+                            log::trace!(target: "child neovim ready", "attempts={i}");
+
+                            return Ok((neovim, io_handle, nvim_proc))
+                        },
+                        Ok(Err(e)) => {
+                            log::trace!(target: "child neovim not ready", "{e}");
 
-                return (neovim, io_handle, nvim_proc)
+                            tokio::time::sleep(conn_opt.connect_interval).await;
+                        },
+                        Err(_) => {},
+                    }
+
+                    i += 1;
+                }
+
+                return Err(Error::Timeout {
+                    address: nvim_listen_addr.display().to_string(),
+                    reason: format!(
+                        "not ready to serve requests after {:?} ({i} attempts)",
+                        started.elapsed()
+                    )
+                })
             }
 
             Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound) => {
-                if i == 256 {
+                if i >= max_attempts {
                     break e
                 }
 
@@ -318,6 +1073,9 @@ async fn create_new_neovim_process_ipc(
 
                         break join_e.into()
                     },
+                    Ready(Ok(Err(spawn_e))) => {
+                        return Err(Error::Spawn(spawn_e))
+                    },
                     Ready(Ok(child)) => {
                         log::error!(target: "child neovim finished", "{child:?}");
 
@@ -327,7 +1085,7 @@ async fn create_new_neovim_process_ipc(
                     Pending => {},
                 }
 
-                tokio::time::sleep(std::time::Duration::from_millis(16)).await;
+                tokio::time::sleep(conn_opt.connect_interval).await;
 
                 i += 1;
             }
@@ -336,22 +1094,131 @@ async fn create_new_neovim_process_ipc(
         }
     };
 
-    panic!("Cannot connect to neovim: attempts={i}, address={nvim_listen_addr:?}, {e:?}");
+    let address = nvim_listen_addr.display().to_string();
+    let reason = format!("{e} after {:?} ({i} attempts)", started.elapsed());
+
+    if i >= max_attempts {
+        Err(Error::Timeout { address, reason })
+    } else {
+        Err(Error::Connect { address, reason })
+    }
+}
+
+
+/// Returns path to socket that child neovim should listen:
+/// either provided by user or unique one in temporary directory
+fn determine_child_socket_path(
+    tmp_dir: &Path,
+    page_id: u128,
+    socket: &Option<String>
+) -> Result<std::path::PathBuf, Error> {
+    // Temporary directory is private to this invocation
+    // unless it's provided by user
+    let Some(socket) = socket else {
+        return Ok(tmp_dir.join(format!("socket-{page_id}")))
+    };
+
+    let socket_path = std::path::PathBuf::from(socket);
+
+    let parent = socket_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    match std::fs::metadata(parent) {
+        Ok(m) if m.is_dir() => {},
+        Ok(_) => return Err(Error::Prepare(format!("socket directory {parent:?} is not a directory"))),
+        Err(e) => return Err(Error::Prepare(format!("cannot access socket directory {parent:?}: {e}"))),
+    }
+
+    // Permission bits don't tell whether this process might create
+    // files there, so it's probed as with temporary directory
+    let probe = parent.join(format!(".probe-{page_id}"));
+    let writable = std::fs::File::create(&probe)
+        .and_then(|_| std::fs::remove_file(&probe));
+
+    if let Err(e) = writable {
+        return Err(Error::Prepare(format!("socket directory {parent:?} is not writable: {e}")))
+    }
+
+    Ok(socket_path)
+}
+
+
+/// Starts handling of neovim session over UNIX socket or named pipe
+fn create_ipc_session(
+    ipc: parity_tokio_ipc::Connection,
+    handler: PipeOrSocketHandler
+) -> (
+    Neovim<IoWrite>,
+    tokio::task::JoinHandle<Result<(), Box<nvim_rs::error::LoopError>>>
+) {
+    let (rx, tx) = tokio::io::split(ipc);
+    let (rx, tx) = (IoRead::Ipc(rx.compat()), IoWrite::Ipc(tx.compat_write()));
+    let (nvim, io) = Neovim::<IoWrite>::new(rx, tx, handler);
+    let io_handle = tokio::task::spawn(io);
+
+    (nvim, io_handle)
 }
 
 
 /// This is hack to prevent behavior (or bug) in some shells (see --help[-W])
-fn print_redirect_protection(tmp_dir: &Path) {
+fn print_redirect_protection(tmp_dir: &Path) -> Result<(), Error> {
     let d = tmp_dir
         .join("DO-NOT-REDIRECT-OUTSIDE-OF-NVIM-TERM(--help[-W])");
 
     if let Err(e) = std::fs::create_dir_all(&d) {
-        panic!("Cannot create protection directory '{}': {e:?}", d.display())
+        return Err(Error::Prepare(format!("cannot create protection directory '{}': {e}", d.display())))
     }
 
     println!("{}", d.to_string_lossy());
+
+    Ok(())
+}
+
+/// Builds arguments of child neovim process. Paths are passed as
+/// separate arguments, only custom arguments are split like a shell does
+fn child_nvim_args(
+    config: Option<&str>,
+    custom_args: Option<&str>,
+    nvim_listen_addr: &Path,
+    listen_addr_passing: ListenAddressPassing,
+    notify_ready: bool
+) -> Result<Vec<String>, std::io::Error> {
+    let mut a = vec![
+        String::from("--cmd"),
+        String::from("set shortmess+=I"),
+    ];
+
+    if notify_ready {
+        a.push(String::from("--cmd"));
+        a.push(String::from(
+            "autocmd VimEnter * ++once \
+            call chanclose(sockconnect('pipe', $PAGE_READY_ADDRESS)) \
+            | unlet $PAGE_READY_ADDRESS"
+        ));
+    }
+
+    if let ListenAddressPassing::Argument = listen_addr_passing {
+        a.push(String::from("--listen"));
+        a.push(nvim_listen_addr.to_string_lossy().to_string());
+    }
+
+    if let Some(config) = config {
+        a.push(String::from("-u"));
+        a.push(String::from(config));
+    }
+
+    if let Some(custom_args) = custom_args {
+        let custom_args = shell_words::split(custom_args)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        a.extend(custom_args);
+    }
+
+    Ok(a)
 }
 
+
 /// Spawns child neovim process on top of page,
 /// which further will be connected to page with UNIX socket.
 /// In this way neovim UI is displayed properly on top of page,
@@ -360,48 +1227,116 @@ fn print_redirect_protection(tmp_dir: &Path) {
 /// `neovim_lib`). Also custom neovim config will be picked
 /// if it exists on corresponding locations.
 fn spawn_child_nvim_process(
+    nvim_bin: &str,
+    nvim_env: &[(String, String)],
     config: &Option<String>,
     custom_args: &Option<String>,
-    nvim_listen_addr: &Path
+    nvim_listen_addr: &Path,
+    listen_addr_passing: ListenAddressPassing,
+    ready_addr: Option<&str>
 ) -> Result<ExitStatus, std::io::Error> {
 
-    let nvim_args = {
-        let mut a = String::new();
-        a += "--cmd 'set shortmess+=I' ";
-        a += "--listen ";
-        a += &nvim_listen_addr.to_string_lossy();
-
-        if let Some(config) = config
-            .clone()
-            .or_else(default_config_path)
-        {
-            a += " ";
-            a += "-u ";
-            a += &config;
-        }
+    log::trace!(target: "new neovim process", "Binary: {nvim_bin}");
 
-        if let Some(custom_args) = custom_args.as_ref() {
-            a += " ";
-            a += custom_args;
-        }
+    let listen_addr_passing = listen_addr_passing.resolve(nvim_bin);
+    log::trace!(target: "new neovim process", "Listen address: {listen_addr_passing:?}");
 
-        shell_words::split(&a)
-            .expect("Cannot parse neovim arguments")
-    };
+    let nvim_appname = nvim_env
+        .iter()
+        .rev()
+        .find_map(|(key, val)| (key == "NVIM_APPNAME").then(|| val.clone()))
+        .or_else(|| std::env::var("NVIM_APPNAME").ok())
+        .filter(|appname| !appname.is_empty());
+
+    let config = config
+        .clone()
+        .or_else(|| default_config_path(nvim_appname.as_deref()));
+
+    let nvim_args = child_nvim_args(
+        config.as_deref(),
+        custom_args.as_deref(),
+        nvim_listen_addr,
+        listen_addr_passing,
+        ready_addr.is_some()
+    )?;
 
     log::trace!(target: "new neovim process", "Args: {nvim_args:?}");
 
     let term = current_term();
 
-    std::process::Command::new("nvim")
+    let mut nvim_cmd = std::process::Command::new(nvim_bin);
+    if let ListenAddressPassing::Environment = listen_addr_passing {
+        nvim_cmd.env("NVIM_LISTEN_ADDRESS", nvim_listen_addr);
+    }
+    if let Some(ready_addr) = ready_addr {
+        nvim_cmd.env("PAGE_READY_ADDRESS", ready_addr);
+    }
+    for (key, val) in nvim_env {
+        log::trace!(target: "new neovim process", "Env: {key}={val}");
+
+        nvim_cmd.env(key, val);
+    }
+
+    nvim_cmd
         .args(&nvim_args)
         .stdin(term)
-        .spawn()
-        .expect("Cannot spawn a child neovim process")
+        .spawn()?
         .wait()
 }
 
 
+/// Determines how child neovim process
+/// is told which address it should listen
+#[derive(Debug, Clone, Copy)]
+pub enum ListenAddressPassing {
+    /// Choose by version reported by `nvim --version`
+    Detect,
+    /// Pass `--listen <address>` argument
+    Argument,
+    /// Set `$NVIM_LISTEN_ADDRESS` (for neovim < 0.4 without --listen)
+    Environment,
+}
+
+impl ListenAddressPassing {
+    fn resolve(self, nvim_bin: &str) -> Self {
+        let ListenAddressPassing::Detect = self else {
+            return self
+        };
+
+        let version = std::process::Command::new(nvim_bin)
+            .arg("--version")
+            .output()
+            .ok()
+            .and_then(|o| String::from_utf8(o.stdout).ok())
+            .and_then(|out| parse_nvim_version(&out));
+
+        log::trace!(target: "new neovim process", "Version: {version:?}");
+
+        match version {
+            Some(v) if v < (0, 4) => ListenAddressPassing::Environment,
+            _ => ListenAddressPassing::Argument,
+        }
+    }
+}
+
+/// Parses major and minor versions from `NVIM v0.9.5` line
+fn parse_nvim_version(version_output: &str) -> Option<(u32, u32)> {
+    let version = version_output
+        .lines()
+        .next()?
+        .strip_prefix("NVIM v")?;
+
+    let mut numbers = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(str::parse::<u32>);
+
+    match (numbers.next(), numbers.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => Some((major, minor)),
+        _ => None,
+    }
+}
+
+
 fn current_term() -> std::fs::File {
     #[cfg(windows)]
     let dev = "CON:";
@@ -417,41 +1352,48 @@ fn current_term() -> std::fs::File {
 
 /// Returns path to custom neovim config if
 /// it's present in a corresponding locations
-fn default_config_path() -> Option<String> {
+fn default_config_path(nvim_appname: Option<&str>) -> Option<String> {
     use std::path::PathBuf;
 
-    let page_home = std::env::var("XDG_CONFIG_HOME")
-        .map(|xdg_config_home| {
-            PathBuf::from(xdg_config_home)
-                .join("page")
-        });
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from);
 
-    let page_home = page_home.or_else(|_| std::env::var("HOME")
+    let config_home = config_home.or_else(|_| std::env::var("HOME")
         .map(|home| {
             PathBuf::from(home)
-                .join(".config/page")
+                .join(".config")
         }));
 
-    log::trace!(target: "config", "directory is: {page_home:?}");
+    let Ok(config_home) = config_home else {
+        log::trace!(target: "config", "directory is: {config_home:?}");
 
-    let Ok(page_home) = page_home else {
         return None;
     };
 
-    let init_lua = page_home
-        .join("init.lua");
-    if init_lua.exists() {
-        let p = init_lua.to_string_lossy().to_string();
-        log::trace!(target: "config", "use init.lua");
-        return Some(p)
-    }
+    // Config of $NVIM_APPNAME is preferred to stay consistent with child neovim
+    let page_homes = nvim_appname
+        .map(|appname| config_home.join(appname).join("page"))
+        .into_iter()
+        .chain([config_home.join("page")]);
+
+    for page_home in page_homes {
+        log::trace!(target: "config", "directory is: {page_home:?}");
+
+        let init_lua = page_home
+            .join("init.lua");
+        if init_lua.exists() {
+            let p = init_lua.to_string_lossy().to_string();
+            log::trace!(target: "config", "use init.lua");
+            return Some(p)
+        }
 
-    let init_vim = page_home
-        .join("init.vim");
-    if init_vim.exists() {
-        let p = init_vim.to_string_lossy().to_string();
-        log::trace!(target: "config", "use init.vim");
-        return Some(p)
+        let init_vim = page_home
+            .join("init.vim");
+        if init_vim.exists() {
+            let p = init_vim.to_string_lossy().to_string();
+            log::trace!(target: "config", "use init.vim");
+            return Some(p)
+        }
     }
 
     None
@@ -532,6 +1474,7 @@ mod io_handler {
     pub struct PipeOrSocketHandler {
         pub tx: tokio::sync::mpsc::Sender<NotificationFromNeovim>,
         pub page_id: String,
+        pub follow: bool,
     }
 
     #[async_trait::async_trait]
@@ -544,9 +1487,34 @@ mod io_handler {
             args: Vec<Value>,
             _: Neovim<PipeOrSocketWrite>
         ) -> Result<Value, Value> {
-            log::warn!(target: "unhandled", "{request}: {args:?}");
+            log::trace!(target: "request", "{}: {:?} ", request, args);
+
+            let page_id = args
+                .first()
+                .and_then(Value::as_str);
+
+            let same_page_id = page_id
+                .map_or(false, |page_id| page_id == self.page_id);
+            if !same_page_id {
+                log::warn!(target: "invalid page id", "{page_id:?}");
+
+                return Err(Value::from(format!("invalid page id: {page_id:?}")))
+            }
+
+            match request.as_str() {
+                "page_get_status" => {
+                    Ok(Value::Map(vec![
+                        (Value::from("page_id"), Value::from(self.page_id.as_str())),
+                        (Value::from("follow"), Value::from(self.follow)),
+                    ]))
+                },
 
-            Ok(Value::from(0))
+                unknown => {
+                    log::warn!(target: "unhandled request", "{unknown}");
+
+                    Err(Value::from(format!("unknown request: {unknown}")))
+                }
+            }
         }
 
         async fn handle_notify(
@@ -558,7 +1526,7 @@ mod io_handler {
             log::trace!(target: "notification", "{}: {:?} ", notification, args);
 
             let page_id = args
-                .get(0)
+                .first()
                 .and_then(Value::as_str);
 
             let same_page_id = page_id
@@ -572,12 +1540,12 @@ mod io_handler {
             let notification_from_neovim = match notification.as_str() {
                 "page_fetch_lines" => {
                     let count = args.get(1)
-                        .and_then(Value::as_u64);
+                        .and_then(|v| v.as_i64().or_else(|| v.as_str()?.trim().parse().ok()));
 
-                    if let Some(lines_count) = count {
-                        NotificationFromNeovim::FetchLines(lines_count as usize)
-                    } else {
-                        NotificationFromNeovim::FetchPart
+                    match count {
+                        Some(lines_count) if lines_count < 0 => NotificationFromNeovim::FetchAll,
+                        Some(lines_count) => NotificationFromNeovim::FetchLines(lines_count as usize),
+                        None => NotificationFromNeovim::FetchPart,
                     }
                 },
                 "page_buffer_closed" => {
@@ -591,10 +1559,32 @@ mod io_handler {
                 }
             };
 
-            self.tx
-                .send(notification_from_neovim)
-                .await
-                .expect("Cannot receive notification");
+            self.queue(notification_from_neovim)
+                .await;
+        }
+    }
+
+    impl PipeOrSocketHandler {
+        /// Fetches of the next part are coalesced with ones that
+        /// are already queued, while fetches of exact line count and
+        /// closed buffer must be always noticed, so they wait for room
+        pub(super) async fn queue(&self, notification: NotificationFromNeovim) {
+            use tokio::sync::mpsc::error::TrySendError;
+            match self.tx.try_send(notification) {
+                Ok(_) => {},
+
+                Err(TrySendError::Full(NotificationFromNeovim::FetchPart)) => {
+                    log::info!(target: "notification", "queue is full, fetch coalesced");
+                },
+                Err(TrySendError::Full(notification)) => {
+                    if self.tx.send(notification).await.is_err() {
+                        log::trace!(target: "notification", "receiver is closed");
+                    }
+                },
+                Err(TrySendError::Closed(dropped)) => {
+                    log::trace!(target: "notification", "receiver is closed, dropped: {dropped:?}");
+                },
+            }
         }
     }
 
@@ -602,9 +1592,98 @@ mod io_handler {
     /// This enum represents all notifications
     /// that could be sent from page's commands on neovim side
     #[derive(Debug)]
+    #[cfg_attr(test, derive(PartialEq))]
     pub enum NotificationFromNeovim {
         FetchPart,
         FetchLines(usize),
+        /// Everything that's left until EOF
+        FetchAll,
         BufferClosed,
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tcp_addresses() {
+        for addr in ["127.0.0.1:1234", "[::1]:6666", "localhost:6666"] {
+            assert!(is_tcp_address(addr).await, "{addr}");
+        }
+    }
+
+    #[tokio::test]
+    async fn socket_paths_and_addresses_without_port() {
+        for addr in ["localhost", "::1", "/tmp/nvim.sock", "nvim.sock", r"\\.\pipe\nvim-1234"] {
+            assert!(!is_tcp_address(addr).await, "{addr}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_fetches_of_next_part_are_coalesced_on_full_queue() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let handler = PipeOrSocketHandler { tx, page_id: String::new(), follow: false };
+
+        handler.queue(NotificationFromNeovim::FetchPart).await;
+        handler.queue(NotificationFromNeovim::FetchPart).await;
+
+        let queued = tokio::spawn({
+            let handler = handler.clone();
+            async move {
+                handler.queue(NotificationFromNeovim::FetchLines(5)).await;
+                handler.queue(NotificationFromNeovim::FetchAll).await;
+            }
+        });
+
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(rx.recv().await.unwrap());
+        }
+        queued.await.unwrap();
+
+        assert_eq!(received, [
+            NotificationFromNeovim::FetchPart,
+            NotificationFromNeovim::FetchLines(5),
+            NotificationFromNeovim::FetchAll,
+        ]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn child_nvim_args_keep_config_path_with_space() {
+        let args = child_nvim_args(
+            Some("/home/user/my config/init.lua"),
+            Some("-c 'set nowrap' --clean"),
+            Path::new("/tmp/page dir/socket-1"),
+            ListenAddressPassing::Argument,
+            false
+        )
+            .unwrap();
+
+        assert_eq!(args, [
+            "--cmd", "set shortmess+=I",
+            "--listen", "/tmp/page dir/socket-1",
+            "-u", "/home/user/my config/init.lua",
+            "-c", "set nowrap", "--clean",
+        ]);
+    }
+
+    #[test]
+    fn child_nvim_args_with_environment_listen_address() {
+        let args = child_nvim_args(None, None, Path::new("/tmp/socket"), ListenAddressPassing::Environment, true)
+            .unwrap();
+
+        assert_eq!(args.len(), 4);
+        assert!(!args.iter().any(|a| a == "--listen"));
+        assert!(args[3].contains("$PAGE_READY_ADDRESS"));
+    }
+
+    #[test]
+    fn child_nvim_args_reject_unbalanced_quotes() {
+        let args = child_nvim_args(None, Some("-c 'set nowrap"), Path::new("/tmp/socket"), ListenAddressPassing::Argument, false);
+
+        assert_eq!(args.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,314 @@
+//! A module that strips ANSI escape sequences from text written as
+//! lines of scratch buffer (--backend=buffer), where they would be
+//! displayed literally. SGR colors might be turned into highlights
+
+/// Highlight groups of basic and bright foreground colors,
+/// together with colors they're defined with by default
+pub const COLOR_GROUPS: [(&str, &str); 16] = [
+    ("PageAnsiBlack", "Black"),
+    ("PageAnsiRed", "DarkRed"),
+    ("PageAnsiGreen", "DarkGreen"),
+    ("PageAnsiYellow", "DarkYellow"),
+    ("PageAnsiBlue", "DarkBlue"),
+    ("PageAnsiMagenta", "DarkMagenta"),
+    ("PageAnsiCyan", "DarkCyan"),
+    ("PageAnsiWhite", "LightGray"),
+    ("PageAnsiBrightBlack", "DarkGray"),
+    ("PageAnsiBrightRed", "Red"),
+    ("PageAnsiBrightGreen", "Green"),
+    ("PageAnsiBrightYellow", "Yellow"),
+    ("PageAnsiBrightBlue", "Blue"),
+    ("PageAnsiBrightMagenta", "Magenta"),
+    ("PageAnsiBrightCyan", "Cyan"),
+    ("PageAnsiBrightWhite", "White"),
+];
+
+/// Highlight group of bold text
+pub const BOLD_GROUP: &str = "PageAnsiBold";
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+
+/// Text attributes set by SGR sequences, they're kept
+/// between lines since colors might span several of them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    fg: Option<u8>,
+    bold: bool,
+}
+
+
+/// Highlight of stripped line, columns are byte offsets
+#[derive(Debug)]
+pub struct Highlight {
+    pub group: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+
+/// Removes escape sequences (SGR, cursor movement and other CSI, OSC
+/// and charset selection) and control characters except tabs.
+/// Carriage return discards text before it, like progress bars redraw
+/// the line. Returns stripped line with highlights of SGR colors
+pub fn strip(line: &[u8], style: &mut Style) -> (Vec<u8>, Vec<Highlight>) {
+    let mut text = Vec::with_capacity(line.len());
+    let mut highlights = vec![];
+    let mut run_start = 0;
+
+    let mut i = 0;
+    while i < line.len() {
+        match line[i] {
+            ESC => {
+                let (len, sgr_params) = parse_escape(&line[i..]);
+
+                if let Some(params) = sgr_params {
+                    let next_style = apply_sgr(*style, params);
+
+                    if next_style != *style {
+                        push_highlights(*style, run_start, text.len(), &mut highlights);
+                        run_start = text.len();
+                        *style = next_style;
+                    }
+                }
+
+                i += len;
+            }
+            b'\r' => {
+                text.clear();
+                highlights.clear();
+                run_start = 0;
+                i += 1;
+            }
+            b'\t' => {
+                text.push(b'\t');
+                i += 1;
+            }
+            c if c < 0x20 || c == 0x7f => {
+                i += 1;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    push_highlights(*style, run_start, text.len(), &mut highlights);
+
+    (text, highlights)
+}
+
+
+/// Returns length of escape sequence at the start of `seq`
+/// and parameters of it if it's SGR. Unterminated sequence
+/// takes the rest of `seq`
+fn parse_escape(seq: &[u8]) -> (usize, Option<&[u8]>) {
+    match seq.get(1) {
+        None => (1, None),
+
+        // CSI ends with byte in range @ to ~
+        Some(b'[') => {
+            let Some(end) = seq[2..]
+                .iter()
+                .position(|b| (0x40..=0x7e).contains(b))
+                .map(|p| p + 2) else {
+                return (seq.len(), None)
+            };
+
+            if seq[end] == b'm' {
+                (end + 1, Some(&seq[2..end]))
+            } else {
+                (end + 1, None)
+            }
+        }
+
+        // OSC ends with BEL or with ESC \
+        Some(b']') => {
+            let mut j = 2;
+            while j < seq.len() {
+                if seq[j] == BEL {
+                    return (j + 1, None)
+                }
+                if seq[j] == ESC && seq.get(j + 1) == Some(&b'\\') {
+                    return (j + 2, None)
+                }
+                j += 1;
+            }
+
+            (seq.len(), None)
+        }
+
+        // Other sequences are ESC, intermediate bytes and final byte
+        Some(_) => {
+            let intermediate = seq[1..]
+                .iter()
+                .take_while(|b| (0x20..=0x2f).contains(*b))
+                .count();
+
+            ((intermediate + 2).min(seq.len()), None)
+        }
+    }
+}
+
+
+/// Applies SGR parameters to `style`. Only bold and 16 foreground colors
+/// are tracked, 256 colors beyond them and truecolor reset foreground
+fn apply_sgr(mut style: Style, params: &[u8]) -> Style {
+    let mut codes = params
+        .split(|&b| b == b';' || b == b':')
+        .map(|p| std::str::from_utf8(p)
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok())
+            .unwrap_or(0)
+        );
+
+    while let Some(code) = codes.next() {
+        match code {
+            0 => style = Style::default(),
+            1 => style.bold = true,
+            22 => style.bold = false,
+            30..=37 => style.fg = Some((code - 30) as u8),
+            90..=97 => style.fg = Some((code - 90 + 8) as u8),
+            39 => style.fg = None,
+            38 | 48 => {
+                let fg = match codes.next() {
+                    Some(5) => codes
+                        .next()
+                        .filter(|n| *n < 16)
+                        .map(|n| n as u8),
+                    Some(2) => {
+                        codes.nth(2);
+                        None
+                    }
+                    _ => None,
+                };
+
+                if code == 38 {
+                    style.fg = fg;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    style
+}
+
+
+fn push_highlights(style: Style, start: usize, end: usize, highlights: &mut Vec<Highlight>) {
+    if start == end {
+        return
+    }
+
+    if let Some(fg) = style.fg {
+        let (group, _) = COLOR_GROUPS[usize::from(fg)];
+        highlights.push(Highlight { group, start, end });
+    }
+
+    if style.bold {
+        highlights.push(Highlight { group: BOLD_GROUP, start, end });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_line(line: &[u8], style: &mut Style) -> (String, Vec<(&'static str, usize, usize)>) {
+        let (text, highlights) = strip(line, style);
+        let highlights = highlights
+            .into_iter()
+            .map(|hl| (hl.group, hl.start, hl.end))
+            .collect();
+
+        (String::from_utf8(text).unwrap(), highlights)
+    }
+
+    fn strip_new(line: &[u8]) -> (String, Vec<(&'static str, usize, usize)>) {
+        strip_line(line, &mut Style::default())
+    }
+
+    #[test]
+    fn sgr_reset_ends_highlight() {
+        let mut style = Style::default();
+        let stripped = strip_line(b"\x1b[31mred\x1b[0m plain", &mut style);
+
+        assert_eq!(stripped, ("red plain".to_string(), vec![("PageAnsiRed", 0, 3)]));
+        assert_eq!(style, Style::default());
+    }
+
+    #[test]
+    fn empty_sgr_resets() {
+        assert_eq!(
+            strip_new(b"\x1b[1mbold\x1b[m text"),
+            ("bold text".to_string(), vec![(BOLD_GROUP, 0, 4)])
+        );
+    }
+
+    #[test]
+    fn style_spans_lines() {
+        let mut style = Style::default();
+        strip_line(b"\x1b[32mfirst", &mut style);
+
+        assert_eq!(strip_line(b"second", &mut style), ("second".to_string(), vec![("PageAnsiGreen", 0, 6)]));
+    }
+
+    #[test]
+    fn sgr_256_colors() {
+        assert_eq!(strip_new(b"\x1b[38;5;9mx"), ("x".to_string(), vec![("PageAnsiBrightRed", 0, 1)]));
+
+        // Colors beyond basic 16 aren't highlighted
+        assert_eq!(strip_new(b"\x1b[31m\x1b[38;5;200mx"), ("x".to_string(), vec![]));
+    }
+
+    #[test]
+    fn sgr_truecolor_consumes_its_parameters() {
+        assert_eq!(strip_new(b"\x1b[31m\x1b[38;2;10;20;30mx"), ("x".to_string(), vec![]));
+        assert_eq!(strip_new(b"\x1b[38;2;1;2;3;1mx"), ("x".to_string(), vec![(BOLD_GROUP, 0, 1)]));
+    }
+
+    #[test]
+    fn csi_cursor_movement_is_stripped() {
+        assert_eq!(strip_new(b"a\x1b[2Kb\x1b[10;5Hc\x1b[?25l"), ("abc".to_string(), vec![]));
+    }
+
+    #[test]
+    fn osc_terminated_by_bel() {
+        assert_eq!(strip_new(b"\x1b]0;title\x07text"), ("text".to_string(), vec![]));
+    }
+
+    #[test]
+    fn osc_terminated_by_st() {
+        assert_eq!(
+            strip_new(b"\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            ("link".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn unterminated_escape_takes_rest_of_line() {
+        let mut style = Style::default();
+
+        assert_eq!(strip_line(b"ab\x1b[31", &mut style), ("ab".to_string(), vec![]));
+        assert_eq!(style, Style::default());
+        assert_eq!(strip_new(b"ab\x1b]0;title"), ("ab".to_string(), vec![]));
+        assert_eq!(strip_new(b"ab\x1b"), ("ab".to_string(), vec![]));
+    }
+
+    #[test]
+    fn carriage_return_overwrites_line() {
+        assert_eq!(strip_new(b"50%\r100%"), ("100%".to_string(), vec![]));
+        assert_eq!(
+            strip_new(b"\x1b[31m50%\r100%"),
+            ("100%".to_string(), vec![("PageAnsiRed", 0, 4)])
+        );
+    }
+
+    #[test]
+    fn control_characters_except_tab_are_dropped() {
+        assert_eq!(strip_new(b"a\tb\x07c\x7fd"), ("a\tbcd".to_string(), vec![]));
+    }
+}
@@ -3,6 +3,7 @@ use clap::{
     ArgGroup,
     ArgAction,
     ValueHint,
+    ValueEnum,
 };
 
 
@@ -20,33 +21,169 @@ use clap::{
 )]
 pub struct Options {
     /// Set title for output buffer (to display it in statusline)
-    #[clap(display_order=10, short='n', env="PAGE_BUFFER_NAME")]
+    #[clap(display_order=10, short='n', long="name", env="PAGE_BUFFER_NAME")]
     pub name: Option<String>,
 
+    /// Append time when output buffer was created to its title
+    /// (to distinguish repeated runs) formatted by strftime()
+    /// [empty: %H:%M:%S]
+    #[clap(display_order=10, long="title-timestamp", value_name="FORMAT")]
+    pub title_timestamp: Option<Option<String>>,
+
     /// TCP/IP socket address or path to named pipe listened
     /// by running host neovim process
     #[clap(display_order=100, short='a', env="NVIM")]
     pub address: Option<String>,
 
+    /// Spawn child neovim process even if page is invoked from neovim's
+    /// terminal where $NVIM or $NVIM_LISTEN_ADDRESS is set [overrides -a]
+    #[clap(display_order=100, long="spawn-child")]
+    pub spawn_child: bool,
+
+    /// Token passed to `PageAuthenticate(token)` function defined
+    /// in host neovim config before page issues any other request;
+    /// page exits unless it returns true [ignored without <ADDRESS>]
+    #[clap(display_order=100, long="token", env="PAGE_TOKEN", hide_env_values=true)]
+    pub token: Option<String>,
+
     /// Arguments that will be passed to child neovim process
     /// spawned when <ADDRESS> is missing
     #[clap(display_order=101, short='A', env="NVIM_PAGE_ARGS")]
     pub arguments: Option<String>,
 
+    /// Neovim binary that will be run as child neovim process
+    /// spawned when <ADDRESS> is missing [nvim: default]
+    #[clap(
+        display_order=101,
+        long="neovim-bin",
+        env="PAGE_NVIM",
+        default_value="nvim",
+        hide_default_value=true,
+        value_hint=ValueHint::CommandName
+    )]
+    pub neovim_bin: String,
+
+    /// Set environment variable for child neovim process spawned
+    /// when <ADDRESS> is missing, page's own environment is kept
+    /// [might be provided multiple times]
+    #[clap(
+        display_order=101,
+        long="env",
+        value_name="KEY=VALUE",
+        value_parser=parse_env,
+        action=ArgAction::Append
+    )]
+    pub env: Vec<(String, String)>,
+
+    /// Set $NVIM_APPNAME for child neovim process spawned when <ADDRESS>
+    /// is missing (to pick config from alternate directory)
+    #[clap(display_order=101, long="nvim-appname", value_name="NAME")]
+    pub nvim_appname: Option<String>,
+
     /// Config that will be used by child neovim process spawned
-    /// when <ADDRESS> is missing [file: $XDG_CONFIG_HOME/page/init.vim]
+    /// when <ADDRESS> is missing [file: $XDG_CONFIG_HOME/page/init.vim
+    /// preceded by $XDG_CONFIG_HOME/$NVIM_APPNAME/page/init.vim]
     #[clap(display_order=102, short='c', value_hint=ValueHint::AnyPath)]
     pub config: Option<String>,
 
+    /// How to pass listen address to child neovim process spawned
+    /// when <ADDRESS> is missing [argument: default, --listen;
+    /// auto: $NVIM_LISTEN_ADDRESS if `nvim --version` is below 0.4]
+    #[clap(
+        display_order=102,
+        long="listen-via",
+        value_enum,
+        default_value="argument",
+        hide_default_value=true
+    )]
+    pub listen_via: ListenVia,
+
+    /// Path of socket that child neovim process spawned when <ADDRESS>
+    /// is missing will listen; neovim that already listens on it will be
+    /// reused [default: unique path in temporary directory]
+    #[clap(display_order=102, long="socket", value_hint=ValueHint::FilePath)]
+    pub socket: Option<String>,
+
+    /// Directory where page keeps socket and redirection protection
+    /// files, it's kept on exit [default: directory unique for each
+    /// invocation in $XDG_RUNTIME_DIR, $TMPDIR or system temporary directory,
+    /// removed on exit]
+    #[clap(display_order=102, long="tmp-dir", env="PAGE_TMP", value_hint=ValueHint::DirPath)]
+    pub tmp_dir: Option<String>,
+
+    /// Print path of socket listened by child neovim process spawned
+    /// when <ADDRESS> is missing, to be used by `nvim --server`;
+    /// nothing else is printed then [disables -W protection]
+    #[clap(display_order=102, long="print-socket")]
+    pub print_socket: bool,
+
+    /// Interval in milliseconds between attempts to connect to child
+    /// neovim process spawned when <ADDRESS> is missing [16: default]
+    #[clap(
+        display_order=102,
+        long="connect-interval-ms",
+        default_value="16",
+        hide_default_value=true
+    )]
+    pub connect_interval_ms: u64,
+
+    /// How long in milliseconds to wait until child neovim process
+    /// spawned when <ADDRESS> is missing serves requests [4096: default]
+    #[clap(
+        display_order=102,
+        long="connect-timeout",
+        default_value="4096",
+        hide_default_value=true
+    )]
+    pub connect_timeout: u64,
+
+    /// Child neovim process spawned when <ADDRESS> is missing connects
+    /// back to page on VimEnter, so it's known to be ready without
+    /// polling its socket [polling is used if handshake fails]
+    #[clap(display_order=102, long="ready-handshake")]
+    pub ready_handshake: bool,
+
+    /// Check every <HEARTBEAT_MS> milliseconds that neovim at <ADDRESS>
+    /// still responds and exit with error once it doesn't, instead of
+    /// finding it out on the next write. With --reconnect connects
+    /// again instead of exiting [0: disabled and default]
+    #[clap(
+        display_order=102,
+        long="heartbeat-ms",
+        default_value="0",
+        hide_default_value=true
+    )]
+    pub heartbeat_ms: u64,
+
+    /// Connect again to <ADDRESS> once connection to neovim is lost
+    /// (e.g. when its socket was replaced) and resend text which
+    /// wasn't written, retrying as with --connect-timeout
+    /// [requires --backend=channel or --backend=buffer]
+    #[clap(display_order=102, long="reconnect")]
+    pub reconnect: bool,
+
+    /// How many notifications from neovim (e.g. :Page fetches) might
+    /// wait to be processed, further fetches of the next part are
+    /// coalesced with queued ones [16: default]
+    #[clap(
+        display_order=102,
+        long="notification-capacity",
+        default_value="16",
+        hide_default_value=true
+    )]
+    pub notification_capacity: usize,
+
     /// Run command  on output buffer after it was created
     /// or connected as instance
-    #[clap(display_order=106, short='E')]
-    pub command_post: Option<String>,
+    /// [might be provided multiple times to run commands in order]
+    #[clap(display_order=106, short='E', action=ArgAction::Append)]
+    pub command_post: Vec<String>,
 
     /// Run lua expr on output buffer after it was created
-    /// or connected as instance {n}
+    /// or connected as instance
+    /// [`...` holds table with page id and buffer number] {n}
     /// ~ ~ ~
-    #[clap(display_order=107, long="E")]
+    #[clap(display_order=107, long="E", visible_alias="lua-post")]
     pub lua_post: Option<String>,
 
     /// Create output buffer with <INSTANCE> tag or use existed
@@ -59,12 +196,49 @@ pub struct Options {
     #[clap(display_order=201, short='I')]
     pub instance_append: Option<String>,
 
+    /// Append text from page's stdin to output buffer titled by -n
+    /// (or its numbered variant like `name(1)`) or create it if missing,
+    /// without marking buffer as instance
+    #[clap(
+        display_order=201,
+        long="reuse-by-title",
+        requires="name",
+        conflicts_with_all=["instance", "instance_append", "title_timestamp"]
+    )]
+    pub reuse_by_title: bool,
+
+    /// Print tag, buffer number and pty device path of each
+    /// output buffer with instance tag separated by tabs, then exit
+    #[clap(display_order=202, long="list-instances")]
+    pub list_instances: bool,
+
+    /// Focus next instance buffer after current one ordered by
+    /// buffer number (wraps around), then exit
+    #[clap(display_order=202, long="focus-next", conflicts_with="focus_prev")]
+    pub focus_next: bool,
+
+    /// Focus previous instance buffer before current one ordered by
+    /// buffer number (wraps around), then exit
+    #[clap(display_order=202, long="focus-prev")]
+    pub focus_prev: bool,
+
+    /// Display instance buffer that isn't visible in split window
+    /// opened by split flags (below current window without them)
+    /// instead of current window when it's focused
+    #[clap(display_order=202, long="focus-split")]
+    pub focus_split: bool,
+
     /// Close  output buffer with <INSTANCE_CLOSE> tag if it exists
-    /// [without other flags revokes implied by defalt -o or -p option] {n}
-    /// ~ ~ ~
+    /// [without other flags revokes implied by defalt -o or -p option]
     #[clap(display_order=202, short='x')]
     pub instance_close: Option<String>,
 
+    /// Buffer variable name under which instance tag is stored
+    /// [page_instance: default] {n}
+    /// ~ ~ ~
+    #[clap(display_order=203, long="instance-var", default_value="page_instance", hide_default_value=true)]
+    pub instance_var: String,
+
     /// Create and use output buffer (to redirect text from page's stdin)
     /// [implied by default unless -x and/or <FILE> provided without
     /// other flags]
@@ -74,9 +248,21 @@ pub struct Options {
     /// Print path of pty device associated with output buffer (to redirect
     /// text from commands respecting output buffer size and preserving colors)
     /// [implied if page isn't piped unless -x and/or <FILE> provided without other flags]
-    #[clap(display_order=2, short='p')]
+    #[clap(display_order=2, short='p', long="print-pty")]
     pub pty_path_print: bool,
 
+    /// Write text from page's stdin into existing <SINK> (e.g. pty of
+    /// tmux pane or named pipe) instead of output buffer; neovim is
+    /// still used to open <FILE> or close instance with -x
+    #[clap(
+        display_order=2,
+        long="sink",
+        value_hint=ValueHint::FilePath,
+        value_parser=parse_sink,
+        conflicts_with="noopen_lines"
+    )]
+    pub sink: Option<std::path::PathBuf>,
+
     /// Cursor follows content of output buffer as it appears
     /// instead of keeping top position (like `tail -f`)
     #[clap(display_order=5, short='f')]
@@ -87,6 +273,32 @@ pub struct Options {
     #[clap(display_order=6, short='F')]
     pub follow_all: bool,
 
+    /// How text is written into output buffer: through PTY of terminal
+    /// that runs `sleep`, through channel of terminal opened by
+    /// nvim_open_term() (where `sleep` isn't available) or as lines
+    /// of scratch buffer (to keep plain text without terminal processing)
+    /// [pty: default; -p prints channel:<ID> or buffer:<NUMBER>]
+    #[clap(
+        display_order=7,
+        long="backend",
+        value_enum,
+        default_value="pty",
+        hide_default_value=true
+    )]
+    pub backend: OutputBackend,
+
+    /// Remove ANSI escape sequences (colors, cursor movement) and
+    /// carriage returns from text written with --backend=buffer,
+    /// where they would be shown literally [requires --backend=buffer]
+    #[clap(display_order=7, long="strip-ansi")]
+    pub strip_ansi: bool,
+
+    /// Highlight text written with --backend=buffer by its basic
+    /// ANSI colors and bold (PageAnsiRed, PageAnsiBold, etc. groups)
+    /// [implies --strip-ansi; requires --backend=buffer]
+    #[clap(display_order=7, long="ansi-highlight")]
+    pub ansi_highlight: bool,
+
     /// Return back to current buffer
     #[clap(display_order=8, short='b')]
     pub back: bool,
@@ -95,6 +307,12 @@ pub struct Options {
     #[clap(display_order=9, short='B')]
     pub back_restore: bool,
 
+    /// Create and prepare output buffer without ever focusing on it
+    /// (to stream logs into unattended buffer or split)
+    /// [-C, -E, --E and -f are ignored]
+    #[clap(display_order=9, long="background")]
+    pub background: bool,
+
     /// Enable PageConnect PageDisconnect autocommands
     #[clap(display_order=103, short='C')]
     pub command_auto: bool,
@@ -116,19 +334,59 @@ pub struct Options {
     pub page_no_protect: bool,
 
     /// Pagerize output when it exceeds <PAGERIZE> lines
-    /// (to view `journalctl`) [default: disabled; empty: 90_000] {n}
-    /// ~ ~ ~
+    /// (to view `journalctl`) [default: disabled; empty: 90_000]
     #[clap(display_order=12, short='z')]
     pub pagerize: Option<Option<usize>>,
 
+    /// Echo count of lines and bytes read once per <PROGRESS_MS>
+    /// milliseconds while page's stdin is read (to watch long streams)
+    #[clap(display_order=13, long="progress-ms", value_name="PROGRESS_MS")]
+    pub progress_ms: Option<u64>,
+
+    /// Ring terminal bell when page's stdin ends
+    /// (to be notified when long running command is done)
+    #[clap(display_order=13, long="bell-on-eof")]
+    pub bell_on_eof: bool,
+
+    /// Show desktop notification with title of output buffer and count
+    /// of lines read when page's stdin ends [via `notify-send` on Linux
+    /// and `osascript` on macOS; skipped if they aren't available]
+    #[clap(display_order=13, long="notify-desktop")]
+    pub notify_desktop: bool,
+
+    /// Do not echo notifications on neovim side (read parts, end
+    /// of input, progress) and skip redraws that aren't required;
+    /// also suppresses --stats, --bell-on-eof and --notify-desktop
+    #[clap(display_order=13, long="quiet")]
+    pub quiet: bool,
+
+    /// Print count of lines and bytes displayed, count of
+    /// :Page fetches served and elapsed time to stderr on exit {n}
+    /// ~ ~ ~
+    #[clap(display_order=13, long="stats")]
+    pub stats: bool,
+
     #[clap(long="pagerize-hidden", hide = true, number_of_values = 2)]
     pub pagerize_hidden: Option<Vec<u128>>,
 
-    /// Open provided file in a separate buffer
+    /// Open provided file in a separate buffer, <FILE>:<LINE>[:<COL>]
+    /// (as printed by `grep -n` or compilers) opens it at that position
     /// [without other flags revokes implied by default -o or -p option]
     #[clap(name="FILE", value_hint=ValueHint::AnyPath)]
     pub files: Vec<FileOption>,
 
+    /// How multiple <FILE> are opened: each in a separate buffer,
+    /// added to arglist, in a separate tab or in a separate split
+    /// [buffers: default; splits are sized by split flags if provided]
+    #[clap(
+        display_order=2,
+        long="open-files",
+        value_enum,
+        default_value="buffers",
+        hide_default_value=true
+    )]
+    pub open_files: FilesOpenMode,
+
 
     #[clap(flatten)]
     pub output: OutputOptions,
@@ -142,50 +400,77 @@ pub struct Options {
 }
 
 impl Options {
+    /// Flags that only affect scratch buffer can't be expressed
+    /// as clap requirement on value of --backend
+    fn check_backend(&self) -> Result<(), &'static str> {
+        if (self.strip_ansi || self.ansi_highlight) && !matches!(self.backend, OutputBackend::Buffer) {
+            return Err("--strip-ansi and --ansi-highlight require --backend=buffer")
+        }
+
+        if self.reconnect && matches!(self.backend, OutputBackend::Pty) {
+            return Err("--reconnect requires --backend=channel or --backend=buffer")
+        }
+
+        Ok(())
+    }
+
     pub fn is_output_implied(&self) -> bool {
         *self.output_implied.get_or_init(||
             self.back ||
             self.back_restore ||
+            self.background ||
             self.follow ||
             self.follow_all ||
             self.output_open ||
             self.pty_path_print ||
             self.instance.is_some() ||
             self.instance_append.is_some() ||
-            self.command_post.is_some() ||
+            self.reuse_by_title ||
+            !self.command_post.is_empty() ||
             self.lua_post.is_some() ||
             self.output.command.is_some() ||
             self.output.lua.is_some() ||
+            self.output.call.is_some() ||
+            !self.output.eval.is_empty() ||
             self.output.pwd ||
-            self.output.filetype != "pager"
+            self.output.cwd.is_some() ||
+            self.output.back_on_close ||
+            self.output.winbar ||
+            self.output.ephemeral ||
+            self.output.autoscroll ||
+            self.output.pin_view ||
+            self.output.filetype != "pager" ||
+            self.output.detect_filetype ||
+            self.output.man
         )
     }
 
 
     pub fn is_output_split_implied(&self) -> bool {
         *self.output_split_implied.get_or_init(||
-            self.output.split.split_left_cols.is_some() ||
-            self.output.split.split_right_cols.is_some() ||
-            self.output.split.split_above_rows.is_some() ||
-            self.output.split.split_below_rows.is_some() ||
-            self.output.split.split_left > 0u8 ||
-            self.output.split.split_right > 0u8 ||
-            self.output.split.split_above > 0u8 ||
-            self.output.split.split_below > 0u8
+            self.output.split.is_split_direction_provided() ||
+            self.output.split.is_floating() ||
+            self.output.split.tab
         )
     }
 
     pub fn pagerized(&mut self) {
         self.arguments = None;
         self.config = None;
-        self.command_post = None;
+        self.command_post = vec![];
         self.lua_post = None;
         self.instance = None;
         self.instance_append = None;
+        self.reuse_by_title = false;
         self.instance_close = None;
         self.page_no_protect = false;
+        self.print_socket = false;
+        self.spawn_child = false;
+        self.stats = false;
         self.output.lua = None;
         self.output.command = None;
+        self.output.call = None;
+        self.output.eval = vec![];
         self.output.noopen_lines = None;
         self.output.split.split_left = 0;
         self.output.split.split_right = 0;
@@ -195,6 +480,15 @@ impl Options {
         self.output.split.split_right_cols = None;
         self.output.split.split_above_rows = None;
         self.output.split.split_below_rows = None;
+        self.output.split.split_equal = false;
+        self.output.split.tab = false;
+        self.output.split.popup = false;
+        self.output.split.float_width = None;
+        self.output.split.float_height = None;
+        self.output.split.float_row = None;
+        self.output.split.float_col = None;
+        self.output.split.float_fit = None;
+        self.output.split.float_autoclose = false;
         self.files = vec![];
     }
 }
@@ -208,9 +502,27 @@ pub struct OutputOptions {
     pub command: Option<String>,
 
     /// Run lua expr on output buffer after it was created
-    #[clap(display_order=105, long="e")]
+    /// [before -e; `...` holds table with page id and buffer number]
+    #[clap(display_order=105, long="e", visible_alias="lua")]
     pub lua: Option<String>,
 
+    /// Call viml function on output buffer after it was created
+    /// [page id and buffer number are passed as arguments]
+    #[clap(display_order=105, long="call", value_name="FUNC_NAME")]
+    pub call: Option<String>,
+
+    /// Evaluate viml <EXPR> and store result in b:<VAR> on output buffer
+    /// before it's prepared (to consume it in PageOpen autocommand)
+    /// [might be provided multiple times]
+    #[clap(
+        display_order=105,
+        long="eval",
+        value_name="EXPR=VAR",
+        value_parser=parse_eval,
+        action=ArgAction::Append
+    )]
+    pub eval: Vec<EvalOption>,
+
     /// Prefetch <NOOPEN_LINES> from page's stdin or [FILE]: if all
     /// input fits then print it to stdout and exit without neovim usage
     /// (to emulate `less --quit-if-one-screen`)
@@ -228,25 +540,116 @@ pub struct OutputOptions {
     /// negative: term height - <QUERY_LINES>;
     /// 0: disabled and default;
     /// <QUERY> is optional and defaults to <QUERY_LINES>;
+    /// negative <QUERY> or :PageAll fetches everything until EOF;
     /// doesn't take effect on <FILE> buffers]
     #[clap(display_order=4, short='q')]
     pub query_lines: Option<Option<isize>>,
 
     /// Set filetype on output buffer (to enable syntax highlighting)
-    /// [pager: default; not works with text echoed by -O]
-    #[clap(display_order=7, short='t', default_value="pager", hide_default_value=true)]
+    /// [pager: default; empty: filetype isn't set;
+    /// not works with text echoed by -O]
+    #[clap(
+        display_order=7,
+        short='t',
+        long="filetype",
+        default_value="pager",
+        hide_default_value=true,
+        value_parser=parse_filetype
+    )]
     pub filetype: String,
 
+    /// Detect filetype of output buffer by its content
+    /// [man: backspace overstrikes; diff: `--- `, `+++ ` and `@@ ` headers;
+    /// json: bracket that opens JSON on the first non-blank line;
+    /// xml, html and git: prefix of the first non-blank line]
+    #[clap(display_order=7, long="detect-filetype")]
+    pub detect_filetype: bool,
+
+    /// Strip backspace overstrikes from page's stdin and set filetype to man
+    /// (to view `man` output) [implies -t man]
+    #[clap(display_order=7, long="man")]
+    pub man: bool,
+
+    /// Write <HEADER> line into output buffer before page's stdin
+    /// (to label output) [{name} is replaced with -n value,
+    /// {id} with page id and {time} with current time]
+    #[clap(display_order=8, long="header")]
+    pub header: Option<String>,
+
+    /// Message echoed when queried part is read (see -q)
+    /// [{lines} is replaced with count of lines read; empty: suppressed]
+    #[clap(display_order=8, long="query-message")]
+    pub query_message: Option<String>,
+
+    /// Message echoed when page's stdin ends with -q
+    /// [empty: suppressed]
+    #[clap(display_order=8, long="eof-message")]
+    pub eof_message: Option<String>,
+
+    /// Write <FOOTER> line into output buffer when page's stdin ends
+    /// (to mark end of output within scrollback)
+    /// [{lines} is replaced with count of lines read]
+    #[clap(display_order=8, long="footer")]
+    pub footer: Option<String>,
+
+    /// View scrolls to the bottom of output buffer as content appears
+    /// without making cursor follow it; stops while view is scrolled
+    /// up and resumes once it's scrolled back to the bottom
+    #[clap(display_order=5, long="autoscroll")]
+    pub autoscroll: bool,
+
+    /// View of output buffer stays pinned while content appears
+    /// (to read earlier part of growing output undisturbed);
+    /// pin is released once cursor is moved to the last line
+    #[clap(display_order=5, long="pin-view")]
+    pub pin_view: bool,
+
+    /// Keep no more than <SCROLLBACK> lines in output buffer
+    /// [100000: default and neovim's maximum; 0: maximum]
+    #[clap(display_order=11, long="scrollback")]
+    pub scrollback: Option<u64>,
+
     /// Do not remap i, I, a, A, u, d, x, q (and r, R with -q) keys
     /// [wouldn't unmap on connected instance output buffer]
     #[clap(display_order=11, short='w')]
     pub writable: bool,
 
-    /// Set $PWD as working directory at output buffer
+    /// Keep output buffer modifiable (to edit captured output
+    /// before saving) while keys are still remapped [implied by -w;
+    /// instance buffer content is still cleared on -i reuse]
+    #[clap(display_order=11, long="modifiable")]
+    pub modifiable: bool,
+
+    /// Make output buffer read-only, unlisted and wiped once it's left
+    /// (to not leave it behind or save it by accident)
+    /// [can't be used with -i, -I or with returning focus back]
+    #[clap(
+        display_order=11,
+        long="ephemeral",
+        conflicts_with_all=["instance", "instance_append", "back", "back_restore", "background"]
+    )]
+    pub ephemeral: bool,
+
+    /// Show winbar on output window with its title, count of lines
+    /// read and whether it follows output (statusline isn't touched)
+    #[clap(display_order=9, long="winbar")]
+    pub winbar: bool,
+
+    /// Return back to window from which page was invoked
+    /// when output buffer is closed
+    #[clap(display_order=9, long="back-on-close")]
+    pub back_on_close: bool,
+
+    /// Set current directory as working directory at output buffer
     /// (to navigate paths with `gf`)
     #[clap(display_order=3, short='P')]
     pub pwd: bool,
 
+    /// Set <CWD> as working directory at output buffer, relative one
+    /// is resolved against page's working directory [implies -P]
+    #[clap(display_order=3, long="cwd", value_hint=ValueHint::DirPath)]
+    pub cwd: Option<String>,
+
 
     #[clap(flatten)]
     pub split: SplitOptions,
@@ -272,6 +675,12 @@ pub struct SplitOptions {
     #[clap(display_order=903, short='d', action=ArgAction::Count)]
     pub split_below: u8,
 
+    /// With any of -r -l -u -d divide editor width or height evenly
+    /// between output window and windows on current tab instead of ratio
+    /// [to tile several outputs uniformly]
+    #[clap(display_order=903, long="split-equal")]
+    pub split_equal: bool,
+
     /// Split left  and resize to <SPLIT_LEFT_COLS>  columns
     #[clap(display_order=904, short='L')]
     pub split_left_cols: Option<u8>,
@@ -289,11 +698,83 @@ pub struct SplitOptions {
     #[clap(display_order=907, short='D')]
     pub split_below_rows: Option<u8>,
 
+    /// Open output buffer in a new tab instead of split
+    #[clap(display_order=907, long="tab")]
+    pub tab: bool,
+
     /// With any of -r -l -u -d -R -L -U -D open floating window instead of split
-    /// [to not overwrite data in the current terminal] {n}
-    /// ~ ~ ~
+    /// [to not overwrite data in the current terminal;
+    /// without them open floating window centered in editor]
     #[clap(display_order=908, short='+')]
     pub popup: bool,
+
+    /// Width  of floating window in columns or in percents of editor width
+    /// [implies -+; default: derived from split flag or 80%]
+    #[clap(display_order=910, long="float-width", value_parser=parse_float_size)]
+    pub float_width: Option<FloatSize>,
+
+    /// Height of floating window in rows or in percents of editor height
+    /// [implies -+; default: derived from split flag or 80%]
+    #[clap(display_order=911, long="float-height", value_parser=parse_float_size)]
+    pub float_height: Option<FloatSize>,
+
+    /// Row of floating window top edge in rows or in percents of editor height
+    /// [implies -+; default: derived from split flag or centered]
+    #[clap(display_order=912, long="float-row", value_parser=parse_float_size)]
+    pub float_row: Option<FloatSize>,
+
+    /// Column of floating window left edge in columns or in percents of editor width
+    /// [implies -+; default: derived from split flag or centered]
+    #[clap(display_order=913, long="float-col", value_parser=parse_float_size)]
+    pub float_col: Option<FloatSize>,
+
+    /// Shrink or grow floating window height to fit its content
+    /// up to <FLOAT_FIT> rows [implies -+; default: editor height]
+    #[clap(display_order=914, long="float-fit", value_name="FLOAT_FIT", num_args=0..=1)]
+    pub float_fit: Option<Option<u16>>,
+
+    /// Close floating window when focus leaves it
+    /// [implies -+; otherwise floating window is kept open] {n}
+    /// ~ ~ ~
+    #[clap(display_order=915, long="float-autoclose")]
+    pub float_autoclose: bool,
+
+    /// Border of floating window opened with -+
+    /// [none, single, double, rounded, solid, shadow
+    /// or comma-separated list of 1, 2, 4 or 8 border characters;
+    /// rounded: default]
+    #[clap(
+        display_order=909,
+        long="float-border",
+        value_parser=parse_float_border,
+        default_value="rounded",
+        hide_default_value=true
+    )]
+    pub float_border: FloatBorder,
+}
+
+impl SplitOptions {
+    pub fn is_floating(&self) -> bool {
+        self.popup ||
+        self.float_width.is_some() ||
+        self.float_height.is_some() ||
+        self.float_row.is_some() ||
+        self.float_col.is_some() ||
+        self.float_fit.is_some() ||
+        self.float_autoclose
+    }
+
+
+    pub fn is_split_direction_provided(&self) -> bool {
+        self.split_left_cols.is_some() ||
+        self.split_right_cols.is_some() ||
+        self.split_above_rows.is_some() ||
+        self.split_below_rows.is_some() ||
+        self.split_left > 0u8 ||
+        self.split_right > 0u8 ||
+        self.split_above > 0u8 ||
+        self.split_below > 0u8
+    }
 }
 
 
@@ -305,13 +786,13 @@ fn instance_use_arg_group() -> ArgGroup {
 
 fn back_arg_group() -> ArgGroup {
     ArgGroup::new("focusing")
-        .args(["back", "back_restore"])
+        .args(["back", "back_restore", "background"])
         .multiple(false)
 }
 
 fn follow_arg_group() -> ArgGroup {
     ArgGroup::new("following")
-        .args(["follow", "follow_all"])
+        .args(["follow", "follow_all", "autoscroll", "pin_view"])
         .multiple(false)
 }
 
@@ -329,12 +810,22 @@ fn splits_arg_group() -> ArgGroup {
             "split_above_rows",
             "split_below_rows"
         ])
+        .arg("tab")
         .multiple(false)
 }
 
 
 pub fn get_options() -> Options {
-    Options::parse()
+    let opt = Options::parse();
+
+    if let Err(e) = opt.check_backend() {
+        use clap::CommandFactory;
+        Options::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, e)
+            .exit()
+    }
+
+    opt
 }
 
 
@@ -342,6 +833,11 @@ pub fn get_options() -> Options {
 pub enum FileOption {
     Uri(String),
     Path(String),
+    PathAt {
+        path: String,
+        line: u64,
+        col: Option<u64>,
+    },
 }
 
 impl From<&std::ffi::OsStr> for FileOption {
@@ -364,6 +860,15 @@ impl From<&std::ffi::OsStr> for FileOption {
                 _ => {}
             }
 
+            // Position is split only from paths that don't exist as is
+            if !std::path::Path::new(s.as_ref()).exists() {
+                if let Some((path, line, col)) = split_file_position(&s) {
+                    if std::path::Path::new(path).exists() {
+                        return FileOption::PathAt { path: String::from(path), line, col }
+                    }
+                }
+            }
+
             return FileOption::Path(String::from(s))
         }
     }
@@ -371,7 +876,320 @@ impl From<&std::ffi::OsStr> for FileOption {
 
 impl FileOption {
     pub fn as_str(&self) -> &str {
-        let (FileOption::Uri(s) | FileOption::Path(s)) = self;
+        let (FileOption::Uri(s) | FileOption::Path(s) | FileOption::PathAt { path: s, .. }) = self;
         s
     }
 }
+
+/// Splits `<FILE>:<LINE>[:<COL>]` into its parts
+fn split_file_position(s: &str) -> Option<(&str, u64, Option<u64>)> {
+    let (rest, last) = s.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+
+    match rest.rsplit_once(':') {
+        Some((path, line)) if !path.is_empty() => match line.parse() {
+            Ok(line) => Some((path, line, Some(last))),
+            Err(_) => Some((rest, last, None)),
+        },
+        _ if !rest.is_empty() => Some((rest, last, None)),
+        _ => None,
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub enum FloatBorder {
+    Style(String),
+    Chars(Vec<String>),
+}
+
+fn parse_float_border(value: &str) -> Result<FloatBorder, String> {
+    const STYLES: [&str; 6] = ["none", "single", "double", "rounded", "solid", "shadow"];
+
+    if STYLES.contains(&value) {
+        return Ok(FloatBorder::Style(String::from(value)))
+    }
+
+    if value.chars().count() == 1 {
+        return Ok(FloatBorder::Chars(vec![String::from(value)]))
+    }
+
+    let chars: Vec<String> = value
+        .split(',')
+        .map(String::from)
+        .collect();
+
+    if ![2, 4, 8].contains(&chars.len()) {
+        return Err(format!(
+            "expected one of {STYLES:?} or comma-separated list \
+            of 1, 2, 4 or 8 border characters"
+        ))
+    }
+
+    if let Some(c) = chars.iter().find(|c| c.chars().count() > 1) {
+        return Err(format!("border character '{c}' is not a single character"))
+    }
+
+    Ok(FloatBorder::Chars(chars))
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum FloatSize {
+    Cells(u16),
+    Percent(u8),
+}
+
+fn parse_float_size(value: &str) -> Result<FloatSize, String> {
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent = percent
+            .parse::<u8>()
+            .map_err(|e| format!("invalid percentage: {e}"))?;
+
+        if percent > 100 {
+            return Err(String::from("percentage must not exceed 100%"))
+        }
+
+        return Ok(FloatSize::Percent(percent))
+    }
+
+    value
+        .parse::<u16>()
+        .map(FloatSize::Cells)
+        .map_err(|e| format!("expected number or percentage: {e}"))
+}
+
+
+#[derive(Debug, Clone)]
+pub struct EvalOption {
+    pub expr: String,
+    pub var: String,
+}
+
+fn parse_eval(value: &str) -> Result<EvalOption, String> {
+    // Expression might contain '=' while variable name can't
+    let Some((expr, var)) = value.rsplit_once('=') else {
+        return Err(String::from("expected <EXPR>=<VAR>"))
+    };
+
+    let is_var_name = var
+        .chars()
+        .enumerate()
+        .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+
+    if expr.is_empty() || var.is_empty() || !is_var_name {
+        return Err(format!("expected <EXPR>=<VAR>, '{var}' is not a valid variable name"))
+    }
+
+    Ok(EvalOption {
+        expr: String::from(expr),
+        var: String::from(var),
+    })
+}
+
+
+fn parse_filetype(value: &str) -> Result<String, String> {
+    let filetype = value.trim();
+
+    // Compound filetypes are joined with dots, e.g. `markdown.pandoc`
+    let is_filetype = filetype
+        .chars()
+        .all(|c| c == '_' || c == '-' || c == '.' || c.is_ascii_alphanumeric());
+
+    if !is_filetype {
+        return Err(format!("'{filetype}' is not a valid filetype"))
+    }
+
+    Ok(String::from(filetype))
+}
+
+
+fn parse_sink(value: &str) -> Result<std::path::PathBuf, String> {
+    let path = std::path::PathBuf::from(value);
+
+    let metadata = std::fs::metadata(&path)
+        .map_err(|e| format!("cannot access '{value}': {e}"))?;
+
+    if metadata.is_dir() {
+        return Err(format!("'{value}' is a directory"))
+    }
+
+    // Permission bits don't tell whether this process might write
+    // there, so it's opened as it will be. Named pipe is opened
+    // without blocking, since otherwise it waits for a reader
+    let mut open_options = std::fs::OpenOptions::new();
+    open_options.append(true);
+
+    #[cfg(unix)]
+    let is_fifo = std::os::unix::fs::FileTypeExt::is_fifo(&metadata.file_type());
+    #[cfg(unix)]
+    if is_fifo {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        const O_NONBLOCK: i32 = 0o4000;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        const O_NONBLOCK: i32 = 0x4;
+
+        std::os::unix::fs::OpenOptionsExt::custom_flags(&mut open_options, O_NONBLOCK);
+    }
+
+    match open_options.open(&path) {
+        Ok(_) => Ok(path),
+
+        // ENXIO is returned for named pipe which has no reader yet
+        #[cfg(unix)]
+        Err(e) if is_fifo && e.raw_os_error() == Some(6) => {
+            Err(format!("'{value}' is a named pipe which nothing reads from"))
+        }
+
+        Err(e) => Err(format!("'{value}' is not writable: {e}")),
+    }
+}
+
+
+fn parse_env(value: &str) -> Result<(String, String), String> {
+    let Some((key, val)) = value.split_once('=') else {
+        return Err(String::from("expected <KEY>=<VALUE>"))
+    };
+
+    if key.is_empty() || key.contains('\0') || val.contains('\0') {
+        return Err(format!("expected <KEY>=<VALUE>, '{key}' is not a valid variable name"))
+    }
+
+    Ok((String::from(key), String::from(val)))
+}
+
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FilesOpenMode {
+    Buffers,
+    Arglist,
+    Tabs,
+    Splits,
+}
+
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputBackend {
+    Pty,
+    Channel,
+    Buffer,
+}
+
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ListenVia {
+    Auto,
+    Argument,
+    Env,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, clap::Error> {
+        Options::try_parse_from(std::iter::once("page").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn conflicting_splits_are_rejected() {
+        for args in [
+            &["-r", "-l"][..],
+            &["-u", "-d"],
+            &["-r", "-R", "10"],
+            &["-L", "5", "-U", "5"],
+            &["-d", "--tab"],
+            &["-D", "3", "--tab"],
+        ] {
+            let e = parse(args).unwrap_err();
+            assert_eq!(e.kind(), clap::error::ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn single_split_is_accepted() {
+        for args in [
+            &["-rrr"][..],
+            &["-l", "--split-equal"],
+            &["-R", "20"],
+            &["-u", "-+"],
+            &["--tab"],
+        ] {
+            assert!(parse(args).is_ok(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn filetype_is_validated() {
+        assert_eq!(parse_filetype(""), Ok(String::new()));
+        assert_eq!(parse_filetype(" markdown.pandoc "), Ok(String::from("markdown.pandoc")));
+        assert_eq!(parse_filetype("c-sharp_2"), Ok(String::from("c-sharp_2")));
+
+        for invalid in ["it's", "two words", "a\tb", "lua\"", "vim')"] {
+            assert!(parse_filetype(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn ansi_flags_require_buffer_backend() {
+        let opt = parse(&["--strip-ansi"]).unwrap();
+        assert!(opt.check_backend().is_err());
+
+        let opt = parse(&["--ansi-highlight", "--backend=channel"]).unwrap();
+        assert!(opt.check_backend().is_err());
+
+        let opt = parse(&["--ansi-highlight", "--backend=buffer"]).unwrap();
+        assert!(opt.check_backend().is_ok());
+    }
+
+    #[test]
+    fn listen_address_is_passed_as_argument_by_default() {
+        assert!(matches!(parse(&[]).unwrap().listen_via, ListenVia::Argument));
+        assert!(matches!(parse(&["--listen-via=auto"]).unwrap().listen_via, ListenVia::Auto));
+    }
+
+    #[test]
+    fn reconnect_requires_rpc_backend() {
+        let opt = parse(&["--reconnect"]).unwrap();
+        assert!(opt.check_backend().is_err());
+
+        for backend in ["--backend=channel", "--backend=buffer"] {
+            let opt = parse(&["--reconnect", backend]).unwrap();
+            assert!(opt.check_backend().is_ok(), "{backend}");
+        }
+
+        let opt = parse(&["--reconnect", "--backend=buffer", "--heartbeat-ms=100"]).unwrap();
+        assert!(opt.check_backend().is_ok());
+    }
+
+    #[test]
+    fn sink_is_checked_by_opening_it() {
+        let dir = std::env::temp_dir().join(format!("page-sink-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(parse(&["--sink", file.to_str().unwrap()]).is_ok());
+
+        assert!(parse(&["--sink", dir.to_str().unwrap()]).is_err());
+        assert!(parse(&["--sink", dir.join("missing").to_str().unwrap()]).is_err());
+
+        #[cfg(unix)]
+        {
+            let fifo = dir.join("fifo");
+            let created = std::process::Command::new("mkfifo")
+                .arg(&fifo)
+                .status()
+                .map_or(false, |s| s.success());
+
+            // Without reader it's rejected instead of blocking
+            if created {
+                let e = parse(&["--sink", fifo.to_str().unwrap()]).unwrap_err();
+                assert!(e.to_string().contains("nothing reads from"), "{e}");
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
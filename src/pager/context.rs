@@ -1,4 +1,4 @@
-/// A module that contains data collected throughout page invocation
+//! A module that contains data collected throughout page invocation
 
 pub use gather_env::Env;
 pub use check_usage::Usage;
@@ -55,6 +55,21 @@ pub mod gather_env {
             opt.pagerize = None;
         }
 
+        // Background output buffer is never focused,
+        // so flags that require focus are ignored
+        if opt.background {
+            opt.command_auto = false;
+            opt.command_post = vec![];
+            opt.lua_post = None;
+            opt.follow = false;
+            opt.follow_all = false;
+        }
+
+        // Man pages are displayed with man filetype
+        if opt.output.man {
+            opt.output.filetype = String::from("man");
+        }
+
         // Fallback for neovim < 8.0 which don't uses $NVIM
         if opt.address.is_none() {
             if let Ok(address) = std::env::var("NVIM_LISTEN_ADDRESS") {
@@ -62,8 +77,9 @@ pub mod gather_env {
             }
         }
 
-        // Treat empty -a value as if it wasn't provided
-        if opt.address.as_deref().map_or(false, str::is_empty) {
+        // Treat empty -a value as if it wasn't provided,
+        // and don't connect to parent neovim with --spawn-child
+        if opt.spawn_child || opt.address.as_deref().map_or(false, str::is_empty) {
             opt.address = None;
         }
 
@@ -85,7 +101,7 @@ pub mod gather_env {
     fn determine_prefetch_usage(
         noopen_lines: Option<Option<isize>>,
         pagerize: Option<Option<usize>>,
-        files: &Vec<crate::cli::FileOption>,
+        files: &[crate::cli::FileOption],
         input_from_pipe: bool
     ) -> (TermHeight, PrefetchLinesUsage) {
         use once_cell::unsync::Lazy;
@@ -186,6 +202,7 @@ pub mod check_usage {
     pub struct Usage {
         pub opt: crate::cli::Options,
         pub tmp_dir: std::path::PathBuf,
+        pub remove_tmp_dir: bool,
         pub page_id: u128,
         pub prefetched_lines: PrefetchedLines,
         pub query_lines_count: usize,
@@ -197,6 +214,11 @@ pub mod check_usage {
         pub fn is_focus_on_existed_instance_buffer_implied(&self) -> bool {
             let Usage { opt, .. } = self;
 
+            // Background output buffer should be never focused
+            if opt.background {
+                return false
+            }
+
             // Should focus in order to scroll buffer down
             opt.follow ||
 
@@ -204,7 +226,7 @@ pub mod check_usage {
             opt.command_auto ||
 
             // User command should run on focused buffer
-            opt.command_post.is_some() ||
+            !opt.command_post.is_empty() ||
 
             // Same with lua user command
             opt.lua_post.is_some() ||
@@ -231,22 +253,29 @@ pub mod check_usage {
 
         let prefetched_lines = PrefetchedLines(vec![]);
 
-        let tmp_dir = create_temp_directory();
-
         let page_id = if let Some([_, page_id]) = opt.pagerize_hidden.as_deref() {
             *page_id
         } else {
             create_page_id()
         };
 
+        let tmp_dir = create_temp_directory(opt.tmp_dir.as_deref(), page_id);
+
+        // Directory provided by user is persistent, and pagerized
+        // page shares directory with page that spawned it
+        let remove_tmp_dir = opt.pagerize_hidden.is_none() && opt.tmp_dir
+            .as_deref()
+            .map_or(true, str::is_empty);
+
         let print_protection = determine_if_should_print_protection(
             input_from_pipe,
-            opt.page_no_protect,
+            opt.page_no_protect || opt.print_socket,
         );
 
         Usage {
             opt,
             tmp_dir,
+            remove_tmp_dir,
             page_id,
             prefetched_lines,
             query_lines_count,
@@ -255,11 +284,47 @@ pub mod check_usage {
         }
     }
 
-    fn create_temp_directory() -> std::path::PathBuf {
-        let d = std::env::temp_dir()
-            .join("neovim-page");
-        std::fs::create_dir_all(&d)
-            .expect("Cannot create temporary directory for page");
+    fn create_temp_directory(tmp_dir: Option<&str>, page_id: u128) -> std::path::PathBuf {
+        // $XDG_RUNTIME_DIR is per-user and not cleaned by tmp reapers,
+        // temp_dir() respects $TMPDIR. Directory is unique for
+        // each invocation so it might be removed on exit
+        let d = tmp_dir
+            .filter(|d| !d.is_empty())
+            .map_or_else(
+                || {
+                    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+                        .filter(|d| !d.is_empty())
+                        .map(|d| std::path::PathBuf::from(d).join("neovim-page"))
+                        .filter(|d| std::fs::create_dir_all(d).is_ok());
+
+                    runtime_dir
+                        .unwrap_or_else(|| std::env::temp_dir().join("neovim-page"))
+                        .join(format!("page-{page_id}"))
+                },
+                std::path::PathBuf::from
+            );
+
+        let mut dir_builder = std::fs::DirBuilder::new();
+        dir_builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut dir_builder, 0o700);
+
+        // Checked up front, since otherwise it fails
+        // later while creating socket or lock
+        let probe = d.join(format!(".probe-{}", std::process::id()));
+        let writable = dir_builder.create(&d)
+            .and_then(|_| std::fs::File::create(&probe))
+            .and_then(|_| std::fs::remove_file(&probe));
+
+        if let Err(e) = writable {
+            eprintln!(
+                "error: Temporary directory {} isn't writable \
+                (use --tmp-dir or $PAGE_TMP to change it): {e}",
+                d.display()
+            );
+            std::process::exit(1)
+        }
+
         d
     }
 
@@ -278,7 +343,7 @@ pub mod check_usage {
     ) -> bool {
         !input_from_pipe && !page_no_protect &&
         std::env::var_os("PAGE_REDIRECTION_PROTECT")
-            .map_or(true, |protect| protect != "" && protect != "0")
+            .map_or(true, |protect| !protect.is_empty() && protect != "0")
     }
 
     pub struct PrefetchedLines(pub Vec<Vec<u8>>);
@@ -389,7 +454,7 @@ pub mod connect_neovim {
         is_output_split_implied: bool,
         is_output_implied: bool,
         instance_close: &Option<String>,
-        files: &Vec<crate::cli::FileOption>,
+        files: &[crate::cli::FileOption],
         input_from_pipe: bool,
     ) -> OutputBufferUsage {
         let mut outp_buf_usage = OutputBufferUsage::Disabled;
@@ -458,12 +523,13 @@ pub mod output_buffer_available {
     #[derive(Debug)]
     pub struct Output {
         pub opt: crate::cli::Options,
-        pub buf_pty_path: std::path::PathBuf,
+        pub buf_sink: crate::neovim::SinkTarget,
         pub prefetched_lines: super::check_usage::PrefetchedLines,
         pub query_lines_count: usize,
         pub inst_usage: super::connect_neovim::InstanceUsage,
         pub input_from_pipe: bool,
         pub restore_initial_buf_focus: RestoreInitialBufferFocus,
+        #[allow(dead_code)]
         pub nvim_child_proc_spawned: bool,
         pub print_output_buf_pty: bool,
         pub page_id: u128,
@@ -491,7 +557,7 @@ pub mod output_buffer_available {
 
     pub fn enter(
         nvim_ctx: super::Neovim,
-        buf_pty_path: std::path::PathBuf
+        buf_sink: crate::neovim::SinkTarget
     ) -> Output {
 
         let super::Neovim {
@@ -518,7 +584,7 @@ pub mod output_buffer_available {
 
         Output {
             opt,
-            buf_pty_path,
+            buf_sink,
             prefetched_lines,
             query_lines_count,
             inst_usage,
@@ -0,0 +1,192 @@
+//! A module that detects filetype of output buffer by its content
+
+/// How many lines are inspected before detection gives up
+const LINES_TO_INSPECT: usize = 64;
+
+/// How many lines with backspace overstrikes are required to detect man
+const MAN_LINES_REQUIRED: usize = 2;
+
+/// Unified diff headers that should follow each other to detect diff
+const DIFF_HEADERS: [&[u8]; 3] = [b"--- ", b"+++ ", b"@@ "];
+
+/// Prefixes of the first non-blank line and filetypes they're detected as.
+/// JSON is detected separately, since `{` or `[` alone might start anything
+const FIRST_LINE_PREFIXES: &[(&[u8], &str)] = &[
+    (b"<?xml", "xml"),
+    (b"<!DOCTYPE html", "html"),
+    (b"<!doctype html", "html"),
+    (b"<html", "html"),
+    (b"commit ", "git"),
+];
+
+
+/// Inspects first lines of page's stdin and
+/// guesses filetype of output buffer from them
+#[derive(Default)]
+pub struct FiletypeDetector {
+    lines_inspected: usize,
+    man_lines: usize,
+    diff_headers_matched: usize,
+    first_line_inspected: bool,
+}
+
+impl FiletypeDetector {
+    /// Returns detected filetype once line gives enough evidence
+    pub fn inspect_line(&mut self, ln: &[u8]) -> Option<&'static str> {
+        self.lines_inspected += 1;
+
+        if has_overstrikes(ln) {
+            self.man_lines += 1;
+        }
+
+        if self.man_lines >= MAN_LINES_REQUIRED {
+            return Some("man")
+        }
+
+        let ln = trim_leading_escapes(ln);
+
+        if !self.first_line_inspected {
+            if let Some(start) = ln.iter().position(|b| !b.is_ascii_whitespace()) {
+                self.first_line_inspected = true;
+
+                if is_json_start(&ln[start..]) {
+                    return Some("json")
+                }
+
+                let filetype = FIRST_LINE_PREFIXES
+                    .iter()
+                    .find(|(prefix, _)| ln[start..].starts_with(prefix))
+                    .map(|(_, filetype)| *filetype);
+                if filetype.is_some() {
+                    return filetype
+                }
+            }
+        }
+
+        if ln.starts_with(DIFF_HEADERS[self.diff_headers_matched]) {
+            self.diff_headers_matched += 1;
+        } else {
+            self.diff_headers_matched = usize::from(ln.starts_with(DIFF_HEADERS[0]));
+        }
+
+        if self.diff_headers_matched == DIFF_HEADERS.len() {
+            return Some("diff")
+        }
+
+        None
+    }
+
+
+    /// Returns true when there's no reason to inspect lines further
+    pub fn is_exhausted(&self) -> bool {
+        self.lines_inspected >= LINES_TO_INSPECT
+    }
+}
+
+
+/// Checks whether line contains `c\bc` (bold) or `_\bc` (underline)
+/// sequences emitted by man when its output isn't a terminal
+fn has_overstrikes(ln: &[u8]) -> bool {
+    ln.windows(3)
+        .any(|w| w[1] == b'\x08' && (w[0] == w[2] || w[0] == b'_'))
+}
+
+
+/// Checks whether line opens JSON object or array: bracket is
+/// followed only by what JSON allows there, e.g. `{"key"` or `[1,`,
+/// so that `{ foo` or `[[bin]]` from TOML aren't taken for JSON
+fn is_json_start(ln: &[u8]) -> bool {
+    let allowed: &[u8] = match ln.first() {
+        Some(b'{') => b"\"}",
+        Some(b'[') => b"{[\"]-0123456789tfn",
+        _ => return false,
+    };
+
+    let rest = &ln[1..];
+    let next_token = rest
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map(|start| &rest[start..]);
+
+    match next_token {
+        // Pretty-printed JSON opens bracket on its own line
+        None => true,
+        // Nested array must be plausible too, unlike `[[bin]]`
+        Some(rest) if ln[0] == b'[' && rest[0] == b'[' => is_json_start(rest),
+        Some(rest) => {
+            let json_literal = [&b"true"[..], b"false", b"null"]
+                .iter()
+                .any(|literal| rest.starts_with(literal));
+
+            allowed.contains(&rest[0]) && (!rest[0].is_ascii_alphabetic() || json_literal)
+        }
+    }
+}
+
+
+/// Skips SGR escape sequences at the beginning of line,
+/// e.g. colors emitted by `git diff --color`
+fn trim_leading_escapes(mut ln: &[u8]) -> &[u8] {
+    while let Some(rest) = ln.strip_prefix(b"\x1B[") {
+        let Some(end) = rest.iter().position(|&b| b == b'm') else {
+            break
+        };
+        ln = &rest[end + 1..];
+    }
+
+    ln
+}
+
+
+/// Removes backspace overstrikes, leaving only characters that
+/// are printed last on each position. Multibyte characters are
+/// removed as whole
+pub fn strip_overstrikes(ln: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+    if !ln.contains(&b'\x08') {
+        return std::borrow::Cow::Borrowed(ln)
+    }
+
+    let mut stripped = Vec::with_capacity(ln.len());
+    for &b in ln {
+        if b != b'\x08' {
+            stripped.push(b);
+            continue
+        }
+
+        // Pop UTF-8 continuation bytes and then the leading byte
+        while let Some(last) = stripped.pop() {
+            if last & 0b1100_0000 != 0b1000_0000 {
+                break
+            }
+        }
+    }
+
+    std::borrow::Cow::Owned(stripped)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(lines: &[&str]) -> Option<&'static str> {
+        let mut detector = FiletypeDetector::default();
+        lines
+            .iter()
+            .find_map(|ln| detector.inspect_line(ln.as_bytes()))
+    }
+
+    #[test]
+    fn json_is_detected_by_plausible_first_line() {
+        for first in ["{", "{\"key\": 1}", "  {}", "[", "[{\"a\": 1}]", "[\"a\"]", "[1, 2]", "[-1]", "[true]", "[]", "[[1]]"] {
+            assert_eq!(detect(&[first]), Some("json"), "{first}");
+        }
+    }
+
+    #[test]
+    fn bracket_prefix_alone_is_not_json() {
+        for first in ["{ foo = 1 }", "{key}", "[[bin]]", "[section]", "[ERROR] failed", "[trace] x", "[[ x ]]", "{[1]}"] {
+            assert_eq!(detect(&[first]), None, "{first}");
+        }
+    }
+}
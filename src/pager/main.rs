@@ -1,6 +1,6 @@
-pub(crate) mod cli;
-pub(crate) mod neovim;
+pub(crate) use connection::{cli, neovim};
 pub(crate) mod context;
+pub(crate) mod detect;
 
 pub type NeovimConnection = connection::NeovimConnection<neovim::Actions>;
 pub type NeovimBuffer = connection::Buffer<connection::IoWrite>;
@@ -37,7 +37,21 @@ mod main {
         if opt.is_output_split_implied() {
             log::warn!(
                 target: "usage",
-                "Split (-r -l -u -d -R -L -U -D) is ignored \
+                "Split (-r -l -u -d -R -L -U -D --tab) is ignored \
+                if address (-a or $NVIM) isn't set"
+            );
+        }
+        if opt.list_instances {
+            log::warn!(
+                target: "usage",
+                "List instances (--list-instances) is ignored \
+                if address (-a or $NVIM) isn't set"
+            );
+        }
+        if opt.focus_next || opt.focus_prev {
+            log::warn!(
+                target: "usage",
+                "Focus instance (--focus-next --focus-prev) is ignored \
                 if address (-a or $NVIM) isn't set"
             );
         }
@@ -56,12 +70,31 @@ async fn validate_files(mut env_ctx: context::Env) {
     log::info!(target: "context", "{env_ctx:#?}");
 
     let files_count = env_ctx.opt.files.len();
-    for i in 0..files_count {
+    canonicalize_files(&mut env_ctx.opt.files);
+
+    let all_files_not_exists = files_count > 0
+        && env_ctx.opt.files.is_empty();
+    if all_files_not_exists &&
+        !env_ctx.input_from_pipe &&
+        !env_ctx.opt.is_output_implied() &&
+        !env_ctx.opt.is_output_split_implied()
+    {
+        std::process::exit(1)
+    }
+
+    prefetch_lines(env_ctx).await;
+}
+
+
+/// Replaces file paths with canonical ones and removes
+/// files that cannot be opened, URIs are kept as is
+fn canonicalize_files(files: &mut Vec<cli::FileOption>) {
+    files.retain_mut(|f| {
 
-        use cli::FileOption::Path;
-        let Path(path) = &mut env_ctx.opt.files[i] else {
+        use cli::FileOption::{Path, PathAt};
+        let (Path(path) | PathAt { path, .. }) = f else {
             // Uri
-            continue
+            return true
         };
 
         match std::fs::canonicalize(&path) {
@@ -70,29 +103,18 @@ async fn validate_files(mut env_ctx: context::Env) {
                 *path = canonical
                     .to_string_lossy()
                     .to_string();
+
+                true
             }
             Err(e) => {
                 log::error!(
                     target: "open file",
                     r#"Cannot open "{path}": {e}"#);
 
-                env_ctx.opt.files
-                    .remove(i);
+                false
             }
         }
-    }
-
-    let all_files_not_exists = files_count > 0
-        && env_ctx.opt.files.is_empty();
-    if all_files_not_exists &&
-        !env_ctx.input_from_pipe &&
-        !env_ctx.opt.is_output_implied() &&
-        !env_ctx.opt.is_output_split_implied()
-    {
-        std::process::exit(1)
-    }
-
-    prefetch_lines(env_ctx).await;
+    });
 }
 
 
@@ -213,7 +235,7 @@ fn dump_prefetched_lines_and_exit(lines: Vec<Vec<u8>>, filetype: &str) -> ! {
             .arg("--plain")
             .arg("--paging=never")
             .arg("--color=always")
-            .arg(&format!("--language={}", filetype))
+            .arg(format!("--language={}", filetype))
             .stdin(std::process::Stdio::piped())
             .spawn();
 
@@ -259,18 +281,89 @@ fn dump_prefetched_lines_and_exit(lines: Vec<Vec<u8>>, filetype: &str) -> ! {
 
 
 async fn connect_neovim(cli_ctx: context::Usage) {
+    use cli::ListenVia;
+    use connection::ListenAddressPassing;
+    let listen_addr_passing = match cli_ctx.opt.listen_via {
+        ListenVia::Auto => ListenAddressPassing::Detect,
+        ListenVia::Argument => ListenAddressPassing::Argument,
+        ListenVia::Env => ListenAddressPassing::Environment,
+    };
+
     log::info!(target: "context", "{cli_ctx:#?}");
 
+    // Instances exist only in neovim that page is invoked from
+    let focus_adjacent_instance = cli_ctx.opt.focus_next || cli_ctx.opt.focus_prev;
+    if (cli_ctx.opt.list_instances || focus_adjacent_instance) && cli_ctx.opt.address.is_none() {
+        std::process::exit(0)
+    }
+
+    // Neovim isn't needed when there's nothing to open in it
+    if let Some(ref sink) = cli_ctx.opt.sink {
+        if cli_ctx.opt.address.is_none() && cli_ctx.opt.files.is_empty() {
+            redirect_to_sink(sink, &cli_ctx.prefetched_lines.0, cli_ctx.input_from_pipe);
+
+            std::process::exit(0)
+        }
+    }
+
     connection::init_panic_hook();
 
+    let mut nvim_env = cli_ctx.opt.env.clone();
+    if let Some(ref appname) = cli_ctx.opt.nvim_appname {
+        nvim_env.push((String::from("NVIM_APPNAME"), appname.clone()));
+    }
+
+    let conn_opt = connection::ConnectionOptions {
+        address: &cli_ctx.opt.address,
+        token: &cli_ctx.opt.token,
+        nvim_bin: &cli_ctx.opt.neovim_bin,
+        nvim_env: &nvim_env,
+        config: &cli_ctx.opt.config,
+        custom_args: &cli_ctx.opt.arguments,
+        socket: &cli_ctx.opt.socket,
+        listen_addr_passing,
+        connect_interval: std::time::Duration::from_millis(cli_ctx.opt.connect_interval_ms),
+        connect_timeout: std::time::Duration::from_millis(cli_ctx.opt.connect_timeout),
+        ready_handshake: cli_ctx.opt.ready_handshake,
+        notification_capacity: cli_ctx.opt.notification_capacity,
+        print_protection: cli_ctx.print_protection,
+        follow: cli_ctx.opt.follow || cli_ctx.opt.follow_all,
+        heartbeat_interval: (cli_ctx.opt.heartbeat_ms > 0)
+            .then(|| std::time::Duration::from_millis(cli_ctx.opt.heartbeat_ms)),
+        remove_tmp_dir: cli_ctx.remove_tmp_dir,
+        reconnect: cli_ctx.opt.reconnect,
+    };
+
     let mut nvim_conn = connection::open(
         &cli_ctx.tmp_dir,
         cli_ctx.page_id,
-        &cli_ctx.opt.address,
-        &cli_ctx.opt.config,
-        &cli_ctx.opt.config,
-        cli_ctx.print_protection
-    ).await;
+        conn_opt
+    ).await
+    .unwrap_or_else(|e| connection::exit_with_error(&e));
+
+    if cli_ctx.opt.list_instances {
+        print_instances(&mut nvim_conn, &cli_ctx.opt.instance_var).await;
+    }
+
+    if focus_adjacent_instance {
+        focus_adjacent_instance_buffer(
+            &mut nvim_conn,
+            &cli_ctx.opt,
+            cli_ctx.opt.focus_next
+        )
+            .await;
+    }
+
+    if cli_ctx.opt.print_socket && cli_ctx.opt.address.is_none() {
+        use std::io::Write;
+        let mut stdout = std::io::stdout().lock();
+
+        if let Err(e) = writeln!(stdout, "{}", nvim_conn.address)
+            .and_then(|_| stdout.flush())
+        {
+            log::error!(target: "print socket", "Cannot print socket path: {e}");
+        }
+    }
 
     let mut nvim_ctx = context::connect_neovim::enter(cli_ctx);
     if nvim_conn.nvim_proc.is_some() {
@@ -282,12 +375,46 @@ async fn connect_neovim(cli_ctx: context::Usage) {
 }
 
 
+/// Prints instance buffers of connected neovim and exits
+async fn print_instances(nvim_conn: &mut NeovimConnection, inst_var: &str) -> ! {
+    let instances = nvim_conn.nvim_actions
+        .list_instances(inst_var)
+        .await
+        .expect("Cannot list instances");
+
+    for (inst_name, buf_nr, sink) in instances {
+        println!("{inst_name}\t{buf_nr}\t{sink}");
+    }
+
+    connection::close_and_exit(nvim_conn).await
+}
+
+
+/// Focuses next or previous instance buffer of connected neovim and exits
+async fn focus_adjacent_instance_buffer(
+    nvim_conn: &mut NeovimConnection,
+    opt: &cli::Options,
+    forward: bool
+) -> ! {
+    let split = opt.focus_split.then_some(&opt.output.split);
+    nvim_conn.nvim_actions
+        .focus_adjacent_instance_buffer(&opt.instance_var, forward, split)
+        .await
+        .expect("Cannot focus on instance buffer");
+
+    connection::close_and_exit(nvim_conn).await
+}
+
+
 async fn manage_page_state(
     nvim_conn: &mut NeovimConnection,
     nvim_ctx: context::Neovim
 ) {
     log::info!(target: "context", "{nvim_ctx:#?}");
 
+    nvim_conn.nvim_actions
+        .set_quiet(nvim_ctx.opt.quiet);
+
     let mut api_actions = neovim_api_usage::begin(nvim_conn, &nvim_ctx);
 
     api_actions
@@ -297,12 +424,39 @@ async fn manage_page_state(
         .display_files()
         .await;
 
+    if let Some(ref sink) = nvim_ctx.opt.sink {
+        redirect_to_sink(sink, &nvim_ctx.prefetched_lines.0, nvim_ctx.input_from_pipe);
+
+        connection::close_and_exit(nvim_conn).await;
+    }
+
     use context::connect_neovim::OutputBufferUsage;
     if let OutputBufferUsage::Disabled = nvim_ctx.outp_buf_usage {
 
         connection::close_and_exit(nvim_conn).await;
     }
 
+    if nvim_ctx.opt.reuse_by_title {
+        if let Some(titled_outp) = api_actions
+            .find_titled_output_buffer()
+            .await
+        {
+            let outp_ctx = context::output_buffer_available::enter(
+                nvim_ctx,
+                titled_outp.sink
+            );
+
+            manage_output_buffer(
+                nvim_conn,
+                titled_outp.buf,
+                outp_ctx
+            )
+                .await;
+
+            return
+        }
+    }
+
     use context::connect_neovim::InstanceUsage;
     if let InstanceUsage::Enabled { name, .. } = &nvim_ctx.inst_usage {
 
@@ -314,7 +468,7 @@ async fn manage_page_state(
 
             let outp_ctx = context::output_buffer_available::enter(
                 nvim_ctx,
-                active_inst_outp.pty_path
+                active_inst_outp.sink
             );
 
             manage_output_buffer(
@@ -331,7 +485,7 @@ async fn manage_page_state(
 
             let mut outp_ctx = context::output_buffer_available::enter(
                 nvim_ctx,
-                new_inst_outp.pty_path
+                new_inst_outp.sink
             );
             outp_ctx
                 .instance_output_buffer_has_been_created();
@@ -351,7 +505,7 @@ async fn manage_page_state(
 
         let outp_ctx = context::output_buffer_available::enter(
             nvim_ctx,
-            new_outp.pty_path
+            new_outp.sink
         );
 
         manage_output_buffer(
@@ -364,6 +518,35 @@ async fn manage_page_state(
 }
 
 
+/// Writes prefetched lines and the rest of stdin into sink provided by --sink
+fn redirect_to_sink(sink: &std::path::Path, prefetched_lines: &[Vec<u8>], input_from_pipe: bool) {
+    log::info!(target: "sink", "{}", sink.display());
+
+    let mut sink_file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(sink)
+        .unwrap_or_else(|e| {
+            log::error!(target: "sink", "Cannot open sink {}: {e}", sink.display());
+            std::process::exit(1)
+        });
+
+    let written = prefetched_lines
+        .iter()
+        .try_for_each(|ln| std::io::Write::write_all(&mut sink_file, ln))
+        .and_then(|_| if input_from_pipe {
+            std::io::copy(&mut std::io::stdin().lock(), &mut sink_file)
+                .map(|_| ())
+        } else {
+            Ok(())
+        });
+
+    if let Err(e) = written {
+        log::error!(target: "sink", "Cannot write into sink {}: {e}", sink.display());
+        std::process::exit(1)
+    }
+}
+
+
 async fn manage_output_buffer(
     nvim_conn: &mut NeovimConnection,
     buf: NeovimBuffer,
@@ -401,6 +584,9 @@ async fn manage_output_buffer(
     outp_buf_actions
         .focus_on_initial_buffer()
         .await;
+    outp_buf_actions
+        .set_floating_window_autoclose()
+        .await;
 
     if outp_ctx.input_from_pipe {
         if outp_ctx.query_lines_count > 0 {
@@ -415,7 +601,7 @@ async fn manage_output_buffer(
     }
 
     if outp_ctx.print_output_buf_pty {
-        println!("{}", outp_ctx.buf_pty_path.to_string_lossy());
+        println!("{}", outp_ctx.buf_sink);
     }
 
     outp_buf_actions
@@ -429,6 +615,31 @@ async fn manage_output_buffer(
 
 
 
+/// Returns title of output buffer: -n value followed by icon
+/// that tells whether page reads from pipe or from redirection
+async fn output_buffer_title(
+    nvim_actions: &mut neovim::Actions,
+    buf_name: Option<&str>,
+    input_from_pipe: bool
+) -> String {
+    let (page_icon_key, page_icon_default) = if input_from_pipe {
+        ("page_icon_pipe", " |")
+    } else {
+        ("page_icon_redirect", " >")
+    };
+    let mut buf_title = nvim_actions
+        .get_var_or(page_icon_key, page_icon_default)
+        .await;
+
+    if let Some(buf_name) = buf_name {
+        buf_title.insert_str(0, buf_name);
+    }
+
+    buf_title
+}
+
+
+
 mod neovim_api_usage {
     use super::{
         NeovimConnection,
@@ -460,7 +671,7 @@ mod neovim_api_usage {
 
             if let Some(ref instance) = opt.instance_close {
                 self.nvim_conn.nvim_actions
-                    .close_instance_buffer(instance)
+                    .close_instance_buffer(&opt.instance_var, instance)
                     .await;
             }
         }
@@ -475,44 +686,41 @@ mod neovim_api_usage {
                 nvim_conn: NeovimConnection {
                     nvim_actions,
                     initial_buf_number,
+                    initial_win_id,
                     initial_win_and_buf,
                     ..
                 },
                 nvim_ctx
             } = self;
 
-            for f in &nvim_ctx.opt.files {
-                if let Err(e) = nvim_actions.open_file_buffer(f.as_str()).await {
-                    log::warn!(target: "page file", r#"Error opening "{f:?}": {e}"#);
-
-                    continue;
-                }
+            let cmd_provided_by_user = &nvim_ctx.opt.output.command.as_deref()
+                .unwrap_or_default();
+            let lua_provided_by_user = &nvim_ctx.opt.output.lua.as_deref()
+                .unwrap_or_default();
+            let writeable = nvim_ctx.opt.output.writable;
+            let modifiable = nvim_ctx.opt.output.modifiable;
 
-                let cmd_provided_by_user = &nvim_ctx.opt.output.command.as_deref()
-                    .unwrap_or_default();
-                let lua_provided_by_user = &nvim_ctx.opt.output.lua.as_deref()
-                    .unwrap_or_default();
-                let writeable = nvim_ctx.opt.output.writable;
-
-                let file_buf_opts = OutputCommands::for_file_buffer(
-                    cmd_provided_by_user,
-                    lua_provided_by_user,
-                    writeable
-                );
+            let file_buf_opts = OutputCommands::for_file_buffer(
+                nvim_ctx.page_id,
+                cmd_provided_by_user,
+                lua_provided_by_user,
+                writeable,
+                modifiable
+            );
 
-                nvim_actions
-                    .prepare_output_buffer(*initial_buf_number, file_buf_opts)
-                    .await;
+            let failed = nvim_actions
+                .open_file_buffers(
+                    &nvim_ctx.opt.files,
+                    nvim_ctx.opt.open_files,
+                    &nvim_ctx.opt.output.split,
+                    (*initial_buf_number, *initial_win_id),
+                    &file_buf_opts,
+                    nvim_ctx.opt.follow_all
+                )
+                .await;
 
-                if nvim_ctx.opt.follow_all {
-                    nvim_actions
-                        .set_current_buffer_follow_output_mode()
-                        .await;
-                } else {
-                    nvim_actions
-                        .set_current_buffer_scroll_mode()
-                        .await;
-                }
+            for (f, e) in failed {
+                log::warn!(target: "page file", r#"Error opening "{f}": {e}"#);
             }
 
             if nvim_ctx.is_split_flag_given_with_files() {
@@ -531,14 +739,44 @@ mod neovim_api_usage {
             &mut self,
             inst_name: &str
         ) -> Option<OutputBuffer> {
+            let inst_var = &self.nvim_ctx.opt.instance_var;
             let outp = self.nvim_conn.nvim_actions
-                .find_instance_buffer(inst_name)
-                .await;
+                .find_instance_buffer(inst_var, inst_name)
+                .await
+                .expect("Cannot find instance buffer");
 
             outp
         }
 
 
+        /// Returns output buffer of earlier page with the same title
+        /// (used with --reuse-by-title) and focuses on it unless
+        /// it should stay in background
+        pub async fn find_titled_output_buffer(&mut self) -> Option<OutputBuffer> {
+            let opt = &self.nvim_ctx.opt;
+            let buf_title = super::output_buffer_title(
+                &mut self.nvim_conn.nvim_actions,
+                opt.name.as_deref(),
+                self.nvim_ctx.input_from_pipe
+            )
+                .await;
+
+            let outp = self.nvim_conn.nvim_actions
+                .find_titled_output_buffer(&buf_title)
+                .await
+                .expect("Cannot find output buffer by title")?;
+
+            if !opt.background {
+                self.nvim_conn.nvim_actions
+                    .focus_buffer(&outp.buf, opt.focus_split.then_some(&opt.output.split))
+                    .await
+                    .expect("Cannot focus on output buffer");
+            }
+
+            Some(outp)
+        }
+
+
         /// Creates a new output buffer
         /// and then marks it as instance buffer
         pub async fn create_instance_output_buffer(
@@ -552,8 +790,9 @@ mod neovim_api_usage {
             self.nvim_conn.nvim_actions
                 .mark_buffer_as_instance(
                     &outp.buf,
+                    &self.nvim_ctx.opt.instance_var,
                     inst_name,
-                    &outp.pty_path.to_string_lossy()
+                    &outp.sink
                 )
                 .await;
 
@@ -568,6 +807,7 @@ mod neovim_api_usage {
                 nvim_conn: NeovimConnection {
                     nvim_actions,
                     initial_buf_number,
+                    initial_win_id,
                     channel,
                     nvim_proc,
                     ..
@@ -577,15 +817,19 @@ mod neovim_api_usage {
 
             let outp = if nvim_proc.is_some() && nvim_ctx.opt.files.is_empty() {
                 nvim_actions
-                    .create_replacing_output_buffer()
+                    .create_replacing_output_buffer(nvim_ctx.opt.backend)
                     .await
             } else if nvim_ctx.outp_buf_usage.is_create_split() {
                 nvim_actions
-                    .create_split_output_buffer(&nvim_ctx.opt.output.split)
+                    .create_split_output_buffer(
+                        &nvim_ctx.opt.output.split,
+                        nvim_ctx.opt.background,
+                        nvim_ctx.opt.backend
+                    )
                     .await
             } else {
                 nvim_actions
-                    .create_switching_output_buffer()
+                    .create_switching_output_buffer(nvim_ctx.opt.background, nvim_ctx.opt.backend)
                     .await
             };
 
@@ -595,25 +839,46 @@ mod neovim_api_usage {
                 u128::from(*channel)
             };
 
-            let outp_buf_opts = OutputCommands::for_output_buffer(
+            for eval_opt in &nvim_ctx.opt.output.eval {
+                nvim_actions
+                    .eval_into_buffer_var(&eval_opt.expr, &eval_opt.var, &outp.buf)
+                    .await;
+            }
+
+            let mut outp_buf_opts = OutputCommands::for_output_buffer(
                 nvim_ctx.page_id,
                 channel,
                 nvim_ctx.query_lines_count,
+                nvim_ctx.opt.follow || nvim_ctx.opt.follow_all,
                 &nvim_ctx.opt.output
             );
+            if nvim_ctx.opt.background && nvim_proc.is_none() {
+                let buf_nr = outp.buf
+                    .get_number()
+                    .await
+                    .expect("Cannot get output buffer number");
+
+                outp_buf_opts.in_background(buf_nr);
+            }
             nvim_actions
-                .prepare_output_buffer(*initial_buf_number, outp_buf_opts)
+                .prepare_output_buffer(*initial_buf_number, *initial_win_id, outp_buf_opts)
                 .await;
 
+            if let Some(ref func_name) = nvim_ctx.opt.output.call {
+                nvim_actions
+                    .call_function_on_buffer(func_name, nvim_ctx.page_id, &outp.buf)
+                    .await;
+            }
+
             outp
         }
     }
 }
 
 mod output_buffer_usage {
-    use super::{NeovimConnection, NeovimBuffer, context::Output};
+    use super::{NeovimConnection, NeovimBuffer, context::Output, detect, neovim};
     use connection::NotificationFromNeovim;
-    use std::io::{Read, Write};
+    use std::io::Write;
 
     /// This struct implements actions that should be done
     /// after output buffer is attached
@@ -623,6 +888,13 @@ mod output_buffer_usage {
         buf: NeovimBuffer,
         sink: Option<Box<dyn std::io::Write>>,
         pagerize_lines_displayed: usize,
+        lines_displayed: usize,
+        float_fitted: bool,
+        float_fit_attempted: Option<std::time::Instant>,
+        filetype_detector: Option<detect::FiletypeDetector>,
+        overstrikes_stripped: bool,
+        stats: OutputStats,
+        progress_notified: std::time::Instant,
         channel: u64,
     }
 
@@ -632,12 +904,24 @@ mod output_buffer_usage {
         buf: NeovimBuffer,
         channel: u64,
     ) -> BufferActions<'a> {
+        let filetype_detector = (
+            outp_ctx.opt.output.detect_filetype &&
+            outp_ctx.opt.output.filetype == "pager"
+        ).then(detect::FiletypeDetector::default);
+
         BufferActions {
             nvim_conn,
             outp_ctx,
             buf,
             sink: None,
             pagerize_lines_displayed: 0,
+            lines_displayed: 0,
+            float_fitted: false,
+            float_fit_attempted: None,
+            filetype_detector,
+            overstrikes_stripped: outp_ctx.opt.output.man,
+            stats: OutputStats::default(),
+            progress_notified: std::time::Instant::now(),
             channel,
         }
     }
@@ -654,17 +938,20 @@ mod output_buffer_usage {
                 ..
             } = self;
 
-            let (page_icon_key, page_icon_default) = if outp_ctx.input_from_pipe {
-                ("page_icon_pipe", " |")
-            } else {
-                ("page_icon_redirect", " >")
-            };
-            let mut buf_title = nvim_actions
-                .get_var_or(page_icon_key, page_icon_default)
+            let mut buf_title = super::output_buffer_title(
+                nvim_actions,
+                outp_ctx.opt.name.as_deref(),
+                outp_ctx.input_from_pipe
+            )
                 .await;
 
-            if let Some(ref buf_name) = outp_ctx.opt.name {
-                buf_title.insert_str(0, buf_name);
+            if let Some(ref format) = outp_ctx.opt.title_timestamp {
+                let time = nvim_actions
+                    .format_current_time(format.as_deref().unwrap_or("%H:%M:%S"))
+                    .await;
+
+                buf_title.push(' ');
+                buf_title.push_str(&time);
             }
 
             nvim_actions
@@ -717,12 +1004,27 @@ mod output_buffer_usage {
                 return
             }
 
-            nvim_actions
-                .focus_instance_buffer(inst_name)
-                .await;
+            if !outp_ctx.opt.background {
+                nvim_actions
+                    .focus_instance_buffer(
+                        &outp_ctx.opt.instance_var,
+                        inst_name,
+                        outp_ctx.opt.focus_split.then_some(&outp_ctx.opt.output.split)
+                    )
+                    .await
+                    .expect("Cannot focus on instance buffer");
+            }
 
             if outp_ctx.inst_usage.is_enabled_and_should_replace_its_content() {
 
+                // Scratch buffer would display escape sequence literally
+                if let neovim::SinkTarget::Buffer(buf_nr) = outp_ctx.buf_sink {
+                    nvim_actions
+                        .clear_buffer_sink(buf_nr)
+                        .await;
+                    return
+                }
+
                 const CLEAR_SCREEN_SEQ: &[u8] = b"\x1B[3J\x1B[H\x1b[2J";
                 self
                     .get_sink()
@@ -738,6 +1040,7 @@ mod output_buffer_usage {
         pub async fn execute_commands(&mut self) {
             let BufferActions {
                 outp_ctx,
+                buf,
                 nvim_conn: NeovimConnection { nvim_actions, .. },
                 ..
             } = self;
@@ -750,10 +1053,10 @@ mod output_buffer_usage {
 
             if let Some(ref lua_expr) = outp_ctx.opt.lua_post {
                 nvim_actions
-                    .execute_command_post_lua(lua_expr)
+                    .execute_command_post_lua(lua_expr, outp_ctx.page_id, buf)
                     .await;
             }
-            if let Some(ref command) = outp_ctx.opt.command_post {
+            for command in &outp_ctx.opt.command_post {
                 nvim_actions
                     .execute_command_post(command)
                     .await;
@@ -774,7 +1077,9 @@ mod output_buffer_usage {
                 ..
             } = self;
 
-            if outp_ctx.inst_usage.is_enabled_but_should_be_unfocused() {
+            if outp_ctx.inst_usage.is_enabled_but_should_be_unfocused() ||
+                outp_ctx.opt.background
+            {
                 return
             }
 
@@ -804,11 +1109,35 @@ mod output_buffer_usage {
         }
 
 
+        /// Closes floating output window when focus leaves it
+        /// if --float-autoclose was provided. This is done after
+        /// all focus switches made by page itself
+        pub async fn set_floating_window_autoclose(&mut self) {
+            let BufferActions {
+                outp_ctx,
+                buf,
+                nvim_conn: NeovimConnection { nvim_actions, .. },
+                ..
+            } = self;
+
+            if !outp_ctx.opt.output.split.float_autoclose {
+                return
+            }
+
+            nvim_actions
+                .close_floating_window_on_leave(buf)
+                .await;
+        }
+
+
         /// Writes lines from stdin directly into PTY device
         /// associated with output buffer.
         pub async fn handle_output(&mut self) {
             log::trace!(target: "output", "handle output");
 
+            self.display_header()
+                .await;
+
             // First write all prefetched lines if any available
             for ln in &self.outp_ctx.prefetched_lines.0[..] {
 
@@ -824,10 +1153,10 @@ mod output_buffer_usage {
             }
 
             // Then copy the rest of lines from stdin into buffer pty
-            let mut ln = Vec::with_capacity(2048);
-            for b in std::io::stdin().bytes() {
+            let mut lines = read_stdin_lines();
+            while let Some(ln) = self.next_stdin_line(&mut lines).await {
 
-                match b {
+                match ln {
                     Err(e) => {
                         log::warn!(
                             target: "output",
@@ -837,33 +1166,44 @@ mod output_buffer_usage {
                         break;
                     }
 
-                    Ok(eol @ b'\n') => {
-                        ln.push(eol);
-
+                    Ok(ln) => {
                         self.display_line(&ln)
                             .await
                             .expect("Cannot write next line");
 
-                        ln.clear();
-
                         if self.outp_ctx
                             .should_pagerize(self.pagerize_lines_displayed)
                         {
                             self.pagerize_output();
                         }
                     }
-
-                    Ok(b) => ln.push(b)
                 }
             }
 
             log::trace!(target: "output", "got EOF");
 
+            self.display_footer();
+
             self.close_sink();
 
-            self.display_line(&[b'\0'])
-                .await
-                .expect("Cannot write EOF sequence");
+            // Only terminal of PTY device consumes EOF sequence
+            if self.outp_ctx.buf_sink.is_pty() {
+                self.display_line(b"\0")
+                    .await
+                    .expect("Cannot write EOF sequence");
+            }
+
+            self.update_winbar()
+                .await;
+
+            if !self.float_fitted {
+                self.fit_floating_window()
+                    .await;
+            }
+
+            self.ring_bell_on_eof()
+                .await;
+            self.notify_desktop_on_eof();
         }
 
 
@@ -872,6 +1212,9 @@ mod output_buffer_usage {
         pub async fn handle_query_output(&mut self) {
             log::trace!(target: "output", "handle query output");
 
+            self.display_header()
+                .await;
+
             let mut state = QueryState::default();
             state.next_part(self.outp_ctx.query_lines_count);
 
@@ -907,10 +1250,10 @@ mod output_buffer_usage {
                 .await;
 
             // Then copy the rest of lines from stdin into buffer pty
-            let mut ln = Vec::with_capacity(2048);
-            for b in std::io::stdin().bytes() {
+            let mut lines = read_stdin_lines();
+            while let Some(ln) = self.next_stdin_line(&mut lines).await {
 
-                match b {
+                match ln {
                     Err(e) => {
                         log::warn!(
                             target: "output",
@@ -920,9 +1263,7 @@ mod output_buffer_usage {
                         break;
                     }
 
-                    Ok(eol @ b'\n') => {
-                        ln.push(eol);
-
+                    Ok(ln) => {
                         self.display_line(&ln)
                             .await
                             .expect("Cannot write next line");
@@ -931,31 +1272,45 @@ mod output_buffer_usage {
                         self.exchange_query_messages(&mut state)
                             .await;
 
-                        ln.clear();
-
                         if self.outp_ctx
                             .should_pagerize(self.pagerize_lines_displayed)
                         {
                             self.pagerize_output();
                         }
                     }
-
-                    Ok(b) => ln.push(b)
                 }
 
             }
 
             log::trace!(target: "output", "got EOF");
 
+            self.display_footer();
+
             self.close_sink();
 
             self.nvim_conn.nvim_actions
-                .notify_query_finished(state.how_many_lines_was_sent())
+                .notify_query_finished(
+                    state.how_many_lines_was_sent(),
+                    self.outp_ctx.opt.output.query_message.as_deref()
+                )
+                .await
+                .expect("Cannot notify query finished");
+            self.update_winbar()
                 .await;
 
             self.nvim_conn.nvim_actions
-                .notify_end_of_input()
+                .notify_end_of_input(self.outp_ctx.opt.output.eof_message.as_deref())
+                .await
+                .expect("Cannot notify end of input");
+
+            if !self.float_fitted {
+                self.fit_floating_window()
+                    .await;
+            }
+
+            self.ring_bell_on_eof()
                 .await;
+            self.notify_desktop_on_eof();
         }
 
 
@@ -966,9 +1321,20 @@ mod output_buffer_usage {
         /// If no such notification was arrived then page crashes
         /// with the received IO error
         async fn display_line(&mut self, ln: &[u8]) -> std::io::Result<()> {
+            self.detect_filetype(ln)
+                .await;
+
+            // Lines inspected before man is detected are stripped too,
+            // backspaces would overwrite the same characters in terminal anyway
+            let ln = if self.overstrikes_stripped || self.filetype_detector.is_some() {
+                detect::strip_overstrikes(ln)
+            } else {
+                std::borrow::Cow::Borrowed(ln)
+            };
+
             let pty = self.get_sink();
 
-            if let Err(e) = pty.write_all(ln) {
+            if let Err(e) = pty.write_all(&ln) {
                 log::info!(target: "writeline", "got error: {e:?}");
 
                 let wait_secs = std::time::Duration::from_secs(1);
@@ -1002,10 +1368,201 @@ mod output_buffer_usage {
             }
 
             self.pagerize_lines_displayed += 1;
+            self.lines_displayed += 1;
+            self.stats.line_has_been_displayed(&ln);
+
+            self.notify_progress_if_due()
+                .await;
+
+            // Window is resized at most once per interval while lines
+            // arrive and once more on EOF, not after every line
+            let fit_due = self.float_fit_attempted
+                .map_or(true, |attempted| attempted.elapsed() >= FLOAT_FIT_INTERVAL);
+            if !self.float_fitted && fit_due {
+                self.fit_floating_window()
+                    .await;
+            }
 
             Ok(())
         }
 
+
+        /// Echoes count of lines and bytes read if --progress-ms
+        /// was provided and that much time passed since last echo
+        async fn notify_progress_if_due(&mut self) {
+            let Some(progress_ms) = self.outp_ctx.opt.progress_ms else {
+                return
+            };
+
+            let interval = std::time::Duration::from_millis(progress_ms);
+            if self.progress_notified.elapsed() < interval {
+                return
+            }
+            self.progress_notified = std::time::Instant::now();
+
+            self.nvim_conn.nvim_actions
+                .notify_progress(self.stats.bytes as u64, self.stats.lines as u64)
+                .await;
+        }
+
+
+        /// Updates count of lines read on winbar if --winbar was provided
+        async fn update_winbar(&mut self) {
+            if !self.outp_ctx.opt.output.winbar {
+                return
+            }
+
+            self.nvim_conn.nvim_actions
+                .update_winbar_lines_read(&self.buf, self.stats.lines)
+                .await;
+        }
+
+
+        /// Rings bell after input ends if --bell-on-eof was provided
+        async fn ring_bell_on_eof(&mut self) {
+            if !self.outp_ctx.opt.bell_on_eof || self.outp_ctx.opt.quiet {
+                return
+            }
+
+            self.nvim_conn.nvim_actions
+                .ring_bell()
+                .await;
+        }
+
+
+        /// Shows desktop notification after input ends if --notify-desktop
+        /// was provided. It's best effort, so failures are only logged
+        fn notify_desktop_on_eof(&mut self) {
+            if !self.outp_ctx.opt.notify_desktop || self.outp_ctx.opt.quiet {
+                return
+            }
+
+            let title = self.outp_ctx.opt.name
+                .as_deref()
+                .unwrap_or("page");
+            let body = format!("end of input: {} lines", self.stats.lines);
+
+            let mut notifier = if cfg!(target_os = "macos") {
+                let script = format!("display notification {body:?} with title {title:?}");
+
+                let mut cmd = std::process::Command::new("osascript");
+                cmd.arg("-e").arg(script);
+                cmd
+            } else {
+                let mut cmd = std::process::Command::new("notify-send");
+                cmd.arg(title).arg(body);
+                cmd
+            };
+
+            let notified = notifier
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn();
+
+            if let Err(e) = notified {
+                log::info!(target: "notify desktop", "Cannot show notification: {e}");
+            }
+        }
+
+
+        /// Writes --header line into output buffer before input.
+        /// It isn't counted as displayed line nor inspected by detection
+        async fn display_header(&mut self) {
+            let Some(header) = &self.outp_ctx.opt.output.header else {
+                return
+            };
+
+            let mut header = header
+                .replace("{name}", self.outp_ctx.opt.name.as_deref().unwrap_or_default())
+                .replace("{id}", &self.outp_ctx.page_id.to_string());
+
+            if header.contains("{time}") {
+                let time = self.nvim_conn.nvim_actions
+                    .format_current_time("%Y-%m-%d %H:%M:%S")
+                    .await;
+
+                header = header.replace("{time}", &time);
+            }
+
+            if let Err(e) = writeln!(self.get_sink(), "{header}") {
+                log::error!(target: "header", "Cannot write header: {e}");
+            }
+        }
+
+
+        /// Writes --footer line into output buffer after input ends.
+        /// It isn't counted as displayed line nor inspected by detection
+        fn display_footer(&mut self) {
+            let Some(footer) = &self.outp_ctx.opt.output.footer else {
+                return
+            };
+
+            let footer = footer
+                .replace("{lines}", &self.stats.lines.to_string());
+
+            if let Err(e) = writeln!(self.get_sink(), "{footer}") {
+                log::error!(target: "footer", "Cannot write footer: {e}");
+            }
+        }
+
+
+        /// Inspects line if --detect-filetype was provided without -t
+        /// and sets filetype on output buffer once it's detected.
+        /// Backspace overstrikes are stripped from further lines
+        /// if output is detected as man page and from inspected ones
+        async fn detect_filetype(&mut self, ln: &[u8]) {
+            let Some(detector) = &mut self.filetype_detector else {
+                return
+            };
+
+            let detected = detector.inspect_line(ln);
+            if detected.is_none() && !detector.is_exhausted() {
+                return
+            }
+
+            self.filetype_detector = None;
+
+            let Some(filetype) = detected else {
+                log::info!(target: "detect", "filetype wasn't detected");
+
+                return
+            };
+
+            log::info!(target: "detect", "detected filetype: {filetype}");
+
+            if filetype == "man" {
+                self.overstrikes_stripped = true;
+            }
+
+            self.nvim_conn.nvim_actions
+                .set_buffer_filetype(&self.buf, filetype)
+                .await;
+        }
+
+
+        /// Resizes floating output window to fit displayed lines
+        /// if --float-fit was provided. Once window reached its
+        /// maximum height it won't be resized anymore
+        async fn fit_floating_window(&mut self) {
+            let BufferActions {
+                outp_ctx,
+                buf,
+                nvim_conn: NeovimConnection { nvim_actions, .. },
+                ..
+            } = self;
+
+            let Some(max_height) = outp_ctx.opt.output.split.float_fit else {
+                self.float_fitted = true;
+                return
+            };
+
+            self.float_fit_attempted = Some(std::time::Instant::now());
+            self.float_fitted = nvim_actions
+                .fit_floating_window_to_content(buf, self.lines_displayed, max_height)
+                .await;
+        }
+
         /// If there's more than -z value lines to read (default `90_000`)
         /// then output will be pagerized through spawning `page -p` and
         /// writing to it's PTY device
@@ -1017,15 +1574,8 @@ mod output_buffer_usage {
             let mut page_args = std::env::args();
             page_args.next(); // skip `page`
 
-            let nvim_addr = if let Some(addr) = &self.outp_ctx.opt.address {
-                addr.clone()
-            } else {
-                std::env::temp_dir()
-                    .join("neovim-page")
-                    .join(&format!("socket-{}", &self.outp_ctx.page_id))
-                    .to_string_lossy()
-                    .to_string()
-            };
+            let nvim_addr = self.nvim_conn.address
+                .clone();
 
             let page_pty = std::process::Command::new("page")
                 .stdin(std::process::Stdio::null())
@@ -1046,15 +1596,10 @@ mod output_buffer_usage {
             let page_pty = String::from_utf8(page_pty)
                 .expect("Non UTF8 `page` output");
 
+            // Buffer of other page isn't updated on reconnect
+            let sink = self.open_sink(&neovim::SinkTarget::parse(page_pty.trim()), None);
             self.sink
-                .replace(
-                    Box::new(
-                        std::fs::OpenOptions::new()
-                            .append(true)
-                            .open(page_pty.trim())
-                            .expect("Cannot open pagerized PTY device")
-                    )
-                );
+                .replace(sink);
         }
 
 
@@ -1067,18 +1612,39 @@ mod output_buffer_usage {
             }
 
             self.nvim_conn.nvim_actions
-                .notify_query_finished(s.how_many_lines_was_sent())
+                .notify_query_finished(
+                    s.how_many_lines_was_sent(),
+                    self.outp_ctx.opt.output.query_message.as_deref()
+                )
+                .await
+                .expect("Cannot notify query finished");
+            self.update_winbar()
                 .await;
 
-            match self.nvim_conn.rx
-                .recv()
-                .await
-            {
-                Some(NotificationFromNeovim::FetchLines(n)) =>
-                    s.next_part(n),
+            let NeovimConnection { rx, connection_lost, .. } = &mut *self.nvim_conn;
+            let notification = tokio::select! {
+                notification = rx.recv() => notification,
+                e = connection_lost.wait() => {
+                    self.exit_on_lost_connection(&e)
+                        .await
+                }
+            };
+
+            match notification {
+                Some(NotificationFromNeovim::FetchLines(n)) => {
+                    self.stats.fetches += 1;
+                    s.next_part(n)
+                },
+
+                Some(NotificationFromNeovim::FetchPart) => {
+                    self.stats.fetches += 1;
+                    s.next_part(self.outp_ctx.query_lines_count)
+                },
 
-                Some(NotificationFromNeovim::FetchPart) =>
-                    s.next_part(self.outp_ctx.query_lines_count),
+                Some(NotificationFromNeovim::FetchAll) => {
+                    self.stats.fetches += 1;
+                    s.next_part(usize::MAX)
+                },
 
                 Some(NotificationFromNeovim::BufferClosed) => {
                     log::info!(target: "output-state", "Buffer closed");
@@ -1149,25 +1715,111 @@ mod output_buffer_usage {
             }
         }
 
+        /// Waits for the next line of stdin, exits
+        /// if heartbeat finds that connection was lost
+        async fn next_stdin_line(
+            &mut self,
+            lines: &mut tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>
+        ) -> Option<std::io::Result<Vec<u8>>> {
+            tokio::select! {
+                ln = lines.recv() => ln,
+                e = self.nvim_conn.connection_lost.wait() => {
+                    self.exit_on_lost_connection(&e)
+                        .await
+                }
+            }
+        }
+
+        /// Closes neovim connection then exits with error
+        /// status code after heartbeat found it lost
+        async fn exit_on_lost_connection(&mut self, e: &connection::Error) -> ! {
+            log::error!(target: "done", "{e}");
+
+            if self.outp_ctx.opt.stats && !self.outp_ctx.opt.quiet {
+                eprintln!("{}", self.stats);
+            }
+
+            connection::close_and_exit_with_error(self.nvim_conn, e).await
+        }
+
         /// Closes neovim connection then exits with 0 status code
         pub async fn done(&mut self) {
             log::trace!(target: "done", "now page can exit");
 
+            if self.outp_ctx.opt.stats && !self.outp_ctx.opt.quiet {
+                eprintln!("{}", self.stats);
+            }
+
             connection::close_and_exit(self.nvim_conn).await;
         }
 
         /// Returns PTY device associated with output buffer.
         /// This function ensures that PTY device is opened only once
         fn get_sink(&mut self) -> &mut Box<dyn std::io::Write> {
+            let sink = match self.sink.take() {
+                Some(sink) => sink,
+                None => self.open_sink(&self.outp_ctx.buf_sink, self.reconnect_fn()),
+            };
+
             self.sink
-                .get_or_insert_with(|| {
-                    Box::new(
-                        std::fs::OpenOptions::new()
-                            .append(true)
-                            .open(&self.outp_ctx.buf_pty_path)
-                            .expect("Cannot open PTY device")
-                    )
+                .insert(sink)
+        }
+
+        /// Opens PTY device, terminal channel
+        /// or scratch buffer of output buffer
+        fn open_sink(
+            &self,
+            sink: &neovim::SinkTarget,
+            reconnect: Option<neovim::ReconnectFn<connection::Neovim<connection::IoWrite>>>
+        ) -> Box<dyn std::io::Write> {
+            match sink {
+                neovim::SinkTarget::Channel(chan) => {
+                    Box::new(self.nvim_conn.nvim_actions.open_channel_sink(*chan, reconnect))
+                }
+                neovim::SinkTarget::Buffer(buf_nr) => {
+                    let opt = &self.outp_ctx.opt;
+                    let follow = opt.follow || opt.follow_all;
+                    Box::new(self.nvim_conn.nvim_actions.open_buffer_sink(
+                        *buf_nr,
+                        follow,
+                        opt.strip_ansi,
+                        opt.ansi_highlight,
+                        reconnect
+                    ))
+                }
+                neovim::SinkTarget::Pty(pty_path) => Box::new(
+                    std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(pty_path)
+                        .expect("Cannot open PTY device")
+                ),
+            }
+        }
+
+        /// Connects again to neovim with --reconnect and makes
+        /// output buffer notify page through the new connection
+        fn reconnect_fn(&self) -> Option<neovim::ReconnectFn<connection::Neovim<connection::IoWrite>>> {
+            let reconnect = self.nvim_conn.reconnect.clone()?;
+            let buf = self.buf.get_value().clone();
+
+            Some(std::sync::Arc::new(move || {
+                let (reconnect, buf) = (reconnect.clone(), buf.clone());
+
+                Box::pin(async move {
+                    let (nvim, channel) = reconnect
+                        .connect()
+                        .await
+                        .map_err(|e| log::error!(target: "reconnect", "{e}"))
+                        .ok()?;
+
+                    let buf = neovim::NeovimApi::buffer(&nvim, buf);
+                    neovim::Actions::from(nvim.clone())
+                        .set_notification_channel(&buf, channel)
+                        .await;
+
+                    Some(nvim)
                 })
+            }))
         }
 
         fn close_sink(&mut self) {
@@ -1177,6 +1829,87 @@ mod output_buffer_usage {
 
     }
 
+    /// Reads complete lines of stdin on a separate thread, so that
+    /// waiting for them might be interrupted. Thread isn't joined,
+    /// it ends on EOF or together with page
+    fn read_stdin_lines() -> tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>> {
+        use std::io::BufRead;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+        std::thread::spawn(move || {
+            let mut stdin = std::io::stdin().lock();
+
+            loop {
+                let mut ln = Vec::with_capacity(2048);
+                let read = match stdin.read_until(b'\n', &mut ln) {
+                    // Incomplete last line isn't displayed
+                    Ok(_) if !ln.ends_with(b"\n") => break,
+                    Ok(_) => Ok(ln),
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+
+                let failed = read.is_err();
+                if tx.blocking_send(read).is_err() || failed {
+                    break
+                }
+            }
+        });
+
+        rx
+    }
+
+    /// How often floating window is resized with --float-fit
+    /// while lines are being read
+    const FLOAT_FIT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+    /// Counts what was displayed in output buffer.
+    /// Printed on exit when --stats is provided
+    struct OutputStats {
+        lines: usize,
+        bytes: usize,
+        fetches: usize,
+        started: std::time::Instant,
+    }
+
+    impl Default for OutputStats {
+        fn default() -> Self {
+            OutputStats {
+                lines: 0,
+                bytes: 0,
+                fetches: 0,
+                started: std::time::Instant::now(),
+            }
+        }
+    }
+
+    impl OutputStats {
+        fn line_has_been_displayed(&mut self, ln: &[u8]) {
+            // EOF sequence isn't counted
+            if ln == b"\0" {
+                return
+            }
+
+            self.lines += 1;
+            self.bytes += ln.len();
+        }
+    }
+
+    impl std::fmt::Display for OutputStats {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "page: {} lines, {} bytes, {} fetches in {:.3}s",
+                self.lines,
+                self.bytes,
+                self.fetches,
+                self.started.elapsed().as_secs_f64()
+            )
+        }
+    }
+
+
     /// Encapsulates state of querying lines from neovim side
     /// with :Page <count> command.
     /// Used only when -q <count> argument is provided
@@ -1208,3 +1941,40 @@ mod output_buffer_usage {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_removed_between_existing_ones() {
+        use cli::FileOption::{Path, Uri};
+
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let existing = |name: &str| manifest
+            .join(name)
+            .to_string_lossy()
+            .to_string();
+
+        let mut files = vec![
+            Path(String::from("/nonexistent1")),
+            Path(existing("Cargo.toml")),
+            Path(String::from("/nonexistent2")),
+            Path(String::from("/nonexistent3")),
+            Uri(String::from("https://example.com")),
+            Path(existing("README.md")),
+        ];
+        canonicalize_files(&mut files);
+
+        let kept = files
+            .iter()
+            .map(cli::FileOption::as_str)
+            .collect::<Vec<_>>();
+        let canonical = |name| std::fs::canonicalize(manifest.join(name))
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(kept, [canonical("Cargo.toml").as_str(), "https://example.com", canonical("README.md").as_str()]);
+    }
+}
@@ -1,184 +1,333 @@
 /// A module that extends neovim api with methods required in page
 use nvim_rs::{neovim::Neovim, error::CallError, Buffer, Window, Value};
 use indoc::{indoc, formatdoc};
-use connection::IoWrite;
+use crate::IoWrite;
 use std::{path::PathBuf, convert::TryFrom};
 
 
-/// This struct wraps `nvim_rs::Neovim` and decorates it
-/// with methods required in page. Results returned from underlying
+/// This struct wraps `nvim_rs::Neovim` (or other `NeovimApi`)
+/// and decorates it with methods required in page. Results returned from underlying
 /// Neovim methods are mostly unwrapped, since we anyway cannot provide
-/// any meaningful falback logic on call side
-pub struct Actions {
-    nvim: Neovim<IoWrite>,
+/// any meaningful falback logic on call side. Methods prefixed with
+/// `try_` and methods which failure might be recovered from return
+/// `Error` instead, so it's up to caller whether to unwrap them
+pub struct Actions<N: NeovimApi = Neovim<IoWrite>> {
+    nvim: N,
+    last_buffer_title: Option<(Value, String)>,
+    quiet: bool,
 }
 
-impl From<Neovim<IoWrite>> for Actions {
-    fn from(nvim: Neovim<IoWrite>) -> Self {
-        Actions { nvim }
+impl<N: NeovimApi> From<N> for Actions<N> {
+    fn from(nvim: N) -> Self {
+        Actions { nvim, last_buffer_title: None, quiet: false }
     }
 }
 
-impl Actions {
-    pub async fn get_current_buffer(&mut self) -> Result<Buffer<IoWrite>, Box<CallError>> {
+impl<N: NeovimApi> Actions<N> {
+    /// Suppresses notifications and redraws that aren't required
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+
+    pub async fn get_current_buffer(&mut self) -> Result<N::Buffer, Box<CallError>> {
         self.nvim
             .get_current_buf()
             .await
     }
 
 
-    pub async fn create_replacing_output_buffer(&mut self) -> OutputBuffer {
-        let cmd = indoc! {"
-            local buf = vim.api.nvim_get_current_buf()
-        "};
-
-        self.create_buffer(cmd)
+    pub async fn create_replacing_output_buffer(&mut self, backend: crate::cli::OutputBackend) -> OutputBuffer<N::Buffer> {
+        self.try_create_replacing_output_buffer(backend)
             .await
             .expect("Error when creating output buffer from current")
     }
 
 
-    pub async fn create_switching_output_buffer(&mut self) -> OutputBuffer {
+    pub async fn try_create_replacing_output_buffer(
+        &mut self,
+        backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
         let cmd = indoc! {"
-            local buf = vim.api.nvim_create_buf(true, false)
-            vim.api.nvim_set_current_buf(buf)
+            local buf = vim.api.nvim_get_current_buf()
         "};
 
-        self.create_buffer(cmd)
+        self.create_buffer(cmd, false, backend)
+            .await
+    }
+
+
+    pub async fn create_switching_output_buffer(
+        &mut self,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> OutputBuffer<N::Buffer> {
+        self.try_create_switching_output_buffer(background, backend)
             .await
             .expect("Error when creating output buffer")
     }
 
 
-    pub async fn create_split_output_buffer(
+    pub async fn try_create_switching_output_buffer(
         &mut self,
-        opt: &crate::cli::SplitOptions
-    ) -> OutputBuffer {
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
+        let cmd = if background {
+            indoc! {"
+                local buf = vim.api.nvim_create_buf(true, false)
+            "}
+        } else {
+            indoc! {"
+                local buf = vim.api.nvim_create_buf(true, false)
+                vim.api.nvim_set_current_buf(buf)
+            "}
+        };
 
-        let cmd = if opt.popup {
+        self.create_buffer(cmd, background, backend)
+            .await
+    }
 
-            let w_ratio = |s| format!("math.floor(((w / 2) * 3) / {})", s + 1);
-            let h_ratio = |s| format!("math.floor(((h / 2) * 3) / {})", s + 1);
 
-            let (w, h, o) = ("w".to_string(), "h".to_string(), "0".to_string());
+    pub async fn create_split_output_buffer(
+        &mut self,
+        opt: &crate::cli::SplitOptions,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> OutputBuffer<N::Buffer> {
+        self.try_create_split_output_buffer(opt, background, backend)
+            .await
+            .expect("Error when creating split output buffer")
+    }
 
-            let (width, height, row, col);
 
-            if opt.split_right != 0 {
-                (width = w_ratio(opt.split_right), height = h, row = &o, col = &w)
+    pub async fn try_create_split_output_buffer(
+        &mut self,
+        opt: &crate::cli::SplitOptions,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
+
+        if opt.is_floating() {
+            return self
+                .try_create_floating_output_buffer(opt, background, backend)
+                .await
+        }
 
-            } else if opt.split_left != 0 {
-                (width = w_ratio(opt.split_left),  height = h, row = &o, col = &o)
+        if opt.tab {
+            return self
+                .try_create_tab_output_buffer(background, backend)
+                .await
+        }
 
-            } else if opt.split_below != 0 {
-                (width = w, height = h_ratio(opt.split_below), row = &h, col = &o)
+        let Some(split_cmd) = split_window_cmd(opt) else {
+            unreachable!()
+        };
 
-            } else if opt.split_above != 0 {
-                (width = w, height = h_ratio(opt.split_above), row = &o, col = &o)
+        let cmd = formatdoc! {"
+            {split_cmd}
+            local buf = vim.api.nvim_create_buf(true, false)
+            vim.api.nvim_set_current_buf(buf)
+        "};
 
-            } else if let Some(split_right_cols) = opt.split_right_cols.map(|x| x.to_string()) {
-                (width = split_right_cols, height = h, row = &o, col = &w)
+        self.create_buffer(&cmd, background, backend)
+            .await
+    }
 
-            } else if let Some(split_left_cols) = opt.split_left_cols.map(|x| x.to_string()) {
-                (width = split_left_cols,  height = h, row = &o, col = &o)
 
-            } else if let Some(split_below_rows) = opt.split_below_rows.map(|x| x.to_string()) {
-                (width = w, height = split_below_rows, row = &h, col = &o)
+    pub async fn try_create_tab_output_buffer(
+        &mut self,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
+        let cmd = indoc! {"
+            vim.cmd 'tabnew'
+            local buf = vim.api.nvim_get_current_buf()
+        "};
 
-            } else if let Some(split_above_rows) = opt.split_above_rows.map(|x| x.to_string()) {
-                (width = w, height = split_above_rows, row = &o, col = &o)
+        self.create_buffer(cmd, background, backend)
+            .await
+    }
 
-            } else {
-                unreachable!()
-            };
 
-            formatdoc! {"
-                local w = vim.api.nvim_win_get_width(0)
-                local h = vim.api.nvim_win_get_height(0)
-                local buf = vim.api.nvim_create_buf(true, false)
-                local win = vim.api.nvim_open_win(buf, true, {{
-                    relative = 'editor',
-                    width = {width},
-                    height = {height},
-                    row = {row},
-                    col = {col}
-                }})
-                vim.api.nvim_set_current_win(win)
-                local winblend = vim.g.page_popup_winblend or 25
-                vim.api.nvim_win_set_option(win, 'winblend', winblend)
-            "}
-        } else {
+    pub async fn create_floating_output_buffer(
+        &mut self,
+        opt: &crate::cli::SplitOptions,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> OutputBuffer<N::Buffer> {
+        self.try_create_floating_output_buffer(opt, background, backend)
+            .await
+            .expect("Error when creating floating output buffer")
+    }
 
-            let w_ratio = |s| format!("' .. tostring(math.floor(((w / 2) * 3) / {})) .. '", s + 1);
-            let h_ratio = |s| format!("' .. tostring(math.floor(((h / 2) * 3) / {})) .. '", s + 1);
 
-            let (a, b) = ("aboveleft", "belowright");
-            let (w, h) = ("winfixwidth", "winfixheight");
-            let (v, z) = ("vsplit", "split");
+    pub async fn try_create_floating_output_buffer(
+        &mut self,
+        opt: &crate::cli::SplitOptions,
+        background: bool,
+        backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
 
-            let (direction, size, split, fix);
+        let w_ratio = |s| format!("math.floor(((w / 2) * 3) / {})", s + 1);
+        let h_ratio = |s| format!("math.floor(((h / 2) * 3) / {})", s + 1);
 
-            if opt.split_right != 0 {
-                (direction = b, size = w_ratio(opt.split_right), split = v, fix = w)
+        let (w, h, o) = ("w".to_string(), "h".to_string(), "0".to_string());
 
-            } else if opt.split_left != 0 {
-                (direction = a,  size = w_ratio(opt.split_left), split = v, fix = w)
+        let (mut width, mut height, mut row, mut col);
 
-            } else if opt.split_below != 0 {
-                (direction = b, size = h_ratio(opt.split_below), split = z, fix = h)
+        if opt.split_right != 0 {
+            (width = w_ratio(opt.split_right), height = h, row = o, col = w)
 
-            } else if opt.split_above != 0 {
-                (direction = a, size = h_ratio(opt.split_above), split = z, fix = h)
+        } else if opt.split_left != 0 {
+            (width = w_ratio(opt.split_left),  height = h, row = o.clone(), col = o)
 
-            } else if let Some(split_right_cols) = opt.split_right_cols.map(|x| x.to_string()) {
-                (direction = b, size = split_right_cols, split = v, fix = w)
+        } else if opt.split_below != 0 {
+            (width = w, height = h_ratio(opt.split_below), row = h, col = o)
 
-            } else if let Some(split_left_cols) = opt.split_left_cols.map(|x| x.to_string()) {
-                (direction = a, size = split_left_cols,  split = v, fix = w)
+        } else if opt.split_above != 0 {
+            (width = w, height = h_ratio(opt.split_above), row = o.clone(), col = o)
 
-            } else if let Some(split_below_rows) = opt.split_below_rows.map(|x| x.to_string()) {
-                (direction = b, size = split_below_rows, split = z, fix = h)
+        } else if let Some(split_right_cols) = opt.split_right_cols.map(|x| x.to_string()) {
+            (width = split_right_cols, height = h, row = o, col = w)
 
-            } else if let Some(split_above_rows) = opt.split_above_rows.map(|x| x.to_string()) {
-                (direction = a, size = split_above_rows, split = z, fix = h)
+        } else if let Some(split_left_cols) = opt.split_left_cols.map(|x| x.to_string()) {
+            (width = split_left_cols,  height = h, row = o.clone(), col = o)
 
-            } else {
-                unreachable!()
-            };
+        } else if let Some(split_below_rows) = opt.split_below_rows.map(|x| x.to_string()) {
+            (width = w, height = split_below_rows, row = h, col = o)
 
-            formatdoc! {"
-                local prev_win = vim.api.nvim_get_current_win()
-                local w = vim.api.nvim_win_get_width(prev_win)
-                local h = vim.api.nvim_win_get_height(prev_win)
-                vim.cmd('{direction} {size}{split}')
-                local buf = vim.api.nvim_create_buf(true, false)
-                vim.api.nvim_set_current_buf(buf)
-                local win = vim.api.nvim_get_current_win()
-                vim.api.nvim_win_set_option(win, '{fix}', true)
-            "}
+        } else if let Some(split_above_rows) = opt.split_above_rows.map(|x| x.to_string()) {
+            (width = w, height = split_above_rows, row = o.clone(), col = o)
+
+        } else {
+            // Centered in editor when no split flag provided
+            (
+                width = "math.floor(ew * 0.8)".to_string(),
+                height = "math.floor(eh * 0.8)".to_string(),
+                row = "math.floor((eh - height) / 2)".to_string(),
+                col = "math.floor((ew - width) / 2)".to_string()
+            )
         };
 
-        self.create_buffer(&cmd)
+        if let Some(float_width) = opt.float_width {
+            width = float_size_to_lua(float_width, "ew");
+        }
+        if let Some(float_height) = opt.float_height {
+            height = float_size_to_lua(float_height, "eh");
+        }
+        if let Some(float_row) = opt.float_row {
+            row = float_size_to_lua(float_row, "eh");
+        }
+        if let Some(float_col) = opt.float_col {
+            col = float_size_to_lua(float_col, "ew");
+        }
+
+        let border = float_border_to_lua(&opt.float_border);
+        let enter = !background;
+
+        let cmd = formatdoc! {"
+            local w = vim.api.nvim_win_get_width(0)
+            local h = vim.api.nvim_win_get_height(0)
+            local ew, eh = vim.o.columns, vim.o.lines - vim.o.cmdheight
+            local width = math.max(1, {width})
+            local height = math.max(1, {height})
+            local row = {row}
+            local col = {col}
+            local buf = vim.api.nvim_create_buf(true, false)
+            local win = vim.api.nvim_open_win(buf, {enter}, {{
+                relative = 'editor',
+                width = width,
+                height = height,
+                row = row,
+                col = col,
+                border = {border}
+            }})
+            local winblend = vim.g.page_popup_winblend or 25
+            vim.api.nvim_win_set_option(win, 'winblend', winblend)
+        "};
+
+        self.create_buffer(&cmd, background, backend)
             .await
-            .expect("Error when creating split output buffer")
     }
 
 
     async fn create_buffer(
         &mut self,
-         window_open_cmd: &str
-    ) -> Result<OutputBuffer, String> {
+         window_open_cmd: &str,
+         background: bool,
+         backend: crate::cli::OutputBackend
+    ) -> Result<OutputBuffer<N::Buffer>, Error> {
+        // In background mode focus is restored to the window that
+        // was active before, within the same call to avoid flicker
+        let restore_focus = if background {
+            "vim.api.nvim_set_current_win(page_prev_win)"
+        } else {
+            ""
+        };
+
+        // Terminal without process is written through its channel,
+        // which is passed instead of PTY path
+        if let crate::cli::OutputBackend::Channel = backend {
+            let cmd = formatdoc! {"
+                local page_prev_win = vim.api.nvim_get_current_win()
+                {window_open_cmd}
+                local chan = vim.api.nvim_open_term(buf, {{}})
+                {restore_focus}
+                return {{ buf, '{CHANNEL_SINK_PREFIX}' .. chan }}
+            "};
+            log::trace!(target: "create buffer", "{cmd}");
+
+            let v = self.nvim
+                .exec_lua(&cmd, vec![])
+                .await?;
+
+            return OutputBuffer::try_from((v, &self.nvim))
+                .map_err(Error::Response)
+        }
+
+        // Scratch buffer is written with nvim_buf_set_lines(),
+        // so its number is passed instead of PTY path
+        if let crate::cli::OutputBackend::Buffer = backend {
+            let cmd = formatdoc! {"
+                local page_prev_win = vim.api.nvim_get_current_win()
+                {window_open_cmd}
+                vim.api.nvim_buf_set_option(buf, 'buftype', 'nofile')
+                vim.api.nvim_buf_set_option(buf, 'swapfile', false)
+                {restore_focus}
+                return {{ buf, '{BUFFER_SINK_PREFIX}' .. buf }}
+            "};
+            log::trace!(target: "create buffer", "{cmd}");
+
+            let v = self.nvim
+                .exec_lua(&cmd, vec![])
+                .await?;
+
+            return OutputBuffer::try_from((v, &self.nvim))
+                .map_err(Error::Response)
+        }
+
         // Shell will be temporarily replaced with /bin/sleep to halt
         // for i32::MAX seconds or 68 years
         let cmd = formatdoc! {"
             local shell, shellcmdflag = vim.o.shell, vim.o.shellcmdflag
             vim.o.shell, vim.o.shellcmdflag = 'sleep', ''
+            local page_prev_win = vim.api.nvim_get_current_win()
             {window_open_cmd}
-            local chan = vim.api.nvim_call_function('termopen', {{ '2147483647' }})
+            local chan = vim.api.nvim_buf_call(buf, function()
+                return vim.api.nvim_call_function('termopen', {{ '2147483647' }})
+            end)
             vim.o.shell, vim.o.shellcmdflag = shell, shellcmdflag
-            local pty = vim.api.nvim_get_chan_info(chan).pty
+            {restore_focus}
+            local ok, chan_info = pcall(vim.api.nvim_get_chan_info, chan)
+            if not ok or type(chan_info) ~= 'table' then
+                error('Cannot get info of terminal channel ' .. chan ..
+                    ', nvim_get_chan_info is required (neovim 0.5+)')
+            end
+            local pty = chan_info.pty
             if pty == nil or pty == '' then
-                error 'No PTY on channel'
+                error('No PTY on terminal channel ' .. chan ..
+                    ', output buffer is not connected to PTY device')
             end
             return {{ buf, pty }}
         "};
@@ -186,29 +335,30 @@ impl Actions {
 
         let v = self.nvim
             .exec_lua(&cmd, vec![])
-            .await
-            .expect("Cannot create buffer");
+            .await?;
 
         OutputBuffer::try_from((v, &self.nvim))
+            .map_err(Error::Response)
     }
 
 
     pub async fn mark_buffer_as_instance(
         &mut self,
-        buf: &Buffer<IoWrite>,
+        buf: &N::Buffer,
+        inst_var: &str,
         inst_name: &str,
-        inst_pty_path: &str
+        inst_sink: &SinkTarget
     ) {
         let bv = buf.get_value();
-        log::trace!(target: "new instance", "{:?}->{inst_name}->{inst_pty_path}", bv);
+        log::trace!(target: "new instance", "{:?}->{inst_name}->{inst_sink}", bv);
 
         let v = Value::from(vec![
             Value::from(inst_name),
-            Value::from(inst_pty_path)
+            Value::from(inst_sink.to_string())
         ]);
 
         if let Err(e) = buf
-            .set_var("page_instance", v)
+            .set_var(inst_var, v)
             .await
         {
             log::error!(target: "new instance", "Error when setting instance mark: {e}");
@@ -218,17 +368,17 @@ impl Actions {
 
     pub async fn find_instance_buffer(
         &mut self,
+        inst_var: &str,
         inst_name: &str
-    ) -> Option<OutputBuffer> {
-        log::trace!(target: "find instance", "{inst_name}");
+    ) -> Result<Option<OutputBuffer<N::Buffer>>, Error> {
+        log::trace!(target: "find instance", "{inst_var}: {inst_name}");
 
         let value = self
-            .on_instance(inst_name, "return { buf, pty_path }")
-            .await
-            .expect("Cannot find instance buffer");
+            .on_instance(inst_var, inst_name, "return { buf, pty_path }")
+            .await?;
 
         if value.is_nil() {
-            return None
+            return Ok(None)
         }
 
         let buf = OutputBuffer::try_from((value, &self.nvim));
@@ -236,15 +386,57 @@ impl Actions {
             log::error!(target: "find instance", "Wrong response: {e}");
         }
 
-        buf.ok()
+        Ok(buf.ok())
+    }
+
+
+    /// Returns tag, buffer number and sink of each instance buffer
+    pub async fn list_instances(
+        &mut self,
+        inst_var: &str
+    ) -> Result<Vec<(String, i64, SinkTarget)>, Error> {
+        log::trace!(target: "list instances", "{inst_var}");
+
+        let inst_var = lua_string(inst_var);
+        let cmd = formatdoc! {"
+            local instances = {{}}
+            for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+                local ok, inst_val = pcall(vim.api.nvim_buf_get_var, buf, {inst_var})
+                if ok then
+                    local inst_name, pty_path = unpack(inst_val)
+                    table.insert(instances, {{ inst_name, buf, pty_path }})
+                end
+            end
+            return instances
+        "};
+
+        let instances = self.nvim
+            .exec_lua(&cmd, vec![])
+            .await?;
+
+        let instances = instances
+            .as_array()
+            .ok_or_else(|| Error::Response(String::from("Instances are not an array")))?
+            .iter()
+            .filter_map(|inst| match inst.as_array()?.as_slice() {
+                [inst_name, buf, pty_path] => Some((
+                    String::from(inst_name.as_str()?),
+                    buf.as_i64()?,
+                    SinkTarget::parse(pty_path.as_str()?)
+                )),
+                _ => None,
+            })
+            .collect();
+
+        Ok(instances)
     }
 
 
-    pub async fn close_instance_buffer(&mut self, inst_name: &str) {
-        log::trace!(target: "close instance", "{inst_name}");
+    pub async fn close_instance_buffer(&mut self, inst_var: &str, inst_name: &str) {
+        log::trace!(target: "close instance", "{inst_var}: {inst_name}");
 
         if let Err(e) = self
-            .on_instance(inst_name, "vim.api.nvim_buf_delete(buf, {{ force = true }})")
+            .on_instance(inst_var, inst_name, "vim.api.nvim_buf_delete(buf, {{ force = true }})")
             .await
         {
             log::error!(
@@ -255,43 +447,159 @@ impl Actions {
     }
 
 
-    pub async fn focus_instance_buffer(&mut self, inst_name: &str) {
+    pub async fn focus_instance_buffer(
+        &mut self,
+        inst_var: &str,
+        inst_name: &str,
+        split: Option<&crate::cli::SplitOptions>
+    ) -> Result<(), Error> {
         log::trace!(target: "focus instance", "{inst_name}");
 
-        let cmd = indoc! {"
-            local active_buf = vim.api.nvim_get_current_buf()
-            if active_buf == buf then
+        self.on_instance(inst_var, inst_name, &focus_buffer_cmd(split))
+            .await?;
+
+        Ok(())
+    }
+
+
+    /// Focuses window that displays buffer, or displays it
+    /// in current window or in split window
+    pub async fn focus_buffer(
+        &mut self,
+        buf: &N::Buffer,
+        split: Option<&crate::cli::SplitOptions>
+    ) -> Result<(), Error> {
+        log::trace!(target: "focus buffer", "{:?}", buf.get_value());
+
+        let cmd = format!("local buf = ...\n{}", focus_buffer_cmd(split));
+
+        self.nvim
+            .exec_lua(&cmd, vec![buf.get_value().clone()])
+            .await?;
+
+        Ok(())
+    }
+
+
+    /// Focuses instance buffer next to current buffer (or previous to
+    /// it if `forward` is false) ordered by buffer number, wrapping around
+    pub async fn focus_adjacent_instance_buffer(
+        &mut self,
+        inst_var: &str,
+        forward: bool,
+        split: Option<&crate::cli::SplitOptions>
+    ) -> Result<(), Error> {
+        log::trace!(target: "focus adjacent instance", "{inst_var}: forward={forward}");
+
+        let focus_cmd = focus_buffer_cmd(split);
+        let cmd = formatdoc! {"
+            local inst_var, forward = ...
+            local bufs = {{}}
+            for _, b in ipairs(vim.api.nvim_list_bufs()) do
+                if pcall(vim.api.nvim_buf_get_var, b, inst_var) then
+                    table.insert(bufs, b)
+                end
+            end
+            if #bufs == 0 then
                 return
             end
-            for _, win in ipairs(vim.api.nvim_list_wins()) do
-                local win_buf = vim.api.nvim_win_get_buf(win)
-                if win_buf == buf then
-                    vim.api.nvim_set_current_win(win)
-                    return
+            table.sort(bufs)
+            local current = vim.api.nvim_get_current_buf()
+            local buf
+            if forward then
+                buf = bufs[1]
+                for _, b in ipairs(bufs) do
+                    if b > current then
+                        buf = b
+                        break
+                    end
+                end
+            else
+                buf = bufs[#bufs]
+                for i = #bufs, 1, -1 do
+                    if bufs[i] < current then
+                        buf = bufs[i]
+                        break
+                    end
                 end
             end
-            vim.api.nvim_set_current_buf(buf)
+            {focus_cmd}
         "};
 
-        self.on_instance(inst_name, cmd)
-            .await
-            .expect("Cannot focus on instance buffer");
+        self.nvim
+            .exec_lua(&cmd, vec![Value::from(inst_var), Value::from(forward)])
+            .await?;
+
+        Ok(())
+    }
+
+
+    /// Returns output buffer of earlier page that was titled `title`
+    /// or its de-duplicated variant like `title(1)`, exact title is preferred
+    pub async fn find_titled_output_buffer(
+        &mut self,
+        title: &str
+    ) -> Result<Option<OutputBuffer<N::Buffer>>, Error> {
+        log::trace!(target: "find titled", "{title}");
+
+        let cmd = formatdoc! {"
+            local title = ...
+            local full_title = vim.fn.fnamemodify(title, ':p')
+            local found
+            for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+                local name = vim.api.nvim_buf_get_name(buf)
+                local is_output = pcall(vim.api.nvim_buf_get_var, buf, 'page_id')
+                if is_output and name == full_title then
+                    found = buf
+                    break
+                end
+                if is_output and not found and (name:gsub('%(%d+%)$', '')) == full_title then
+                    found = buf
+                end
+            end
+            if not found then
+                return nil
+            end
+            if vim.bo[found].buftype ~= 'terminal' then
+                return {{ found, '{BUFFER_SINK_PREFIX}' .. found }}
+            end
+            local chan_info = vim.api.nvim_get_chan_info(vim.bo[found].channel)
+            if chan_info.pty == nil or chan_info.pty == '' then
+                return {{ found, '{CHANNEL_SINK_PREFIX}' .. chan_info.id }}
+            end
+            return {{ found, chan_info.pty }}
+        "};
+
+        let value = self.nvim
+            .exec_lua(&cmd, vec![Value::from(title)])
+            .await?;
+
+        if value.is_nil() {
+            return Ok(None)
+        }
+
+        OutputBuffer::try_from((value, &self.nvim))
+            .map(Some)
+            .map_err(Error::Response)
     }
 
 
     async fn on_instance(
         &mut self,
+        inst_var: &str,
         inst_name: &str,
         action: &str
     ) -> Result<Value, Box<CallError>> {
+        let inst_var = lua_string(inst_var);
+        let inst_name = lua_string(inst_name);
         let cmd = formatdoc! {"
             for _, buf in ipairs(vim.api.nvim_list_bufs()) do
                 local inst_name, pty_path
                 local ok = pcall(function()
-                    local inst_val = vim.api.nvim_buf_get_var(buf, 'page_instance')
+                    local inst_val = vim.api.nvim_buf_get_var(buf, {inst_var})
                     inst_name, pty_path = unpack(inst_val)
                 end)
-                if ok and inst_name == '{inst_name}' then
+                if ok and inst_name == {inst_name} then
                     {action}
                 end
             end
@@ -303,78 +611,96 @@ impl Actions {
     }
 
 
+    /// Sets buffer title, if it's already taken by another buffer
+    /// then the first free numbered variant of it is chosen,
+    /// e.g. `title(1)`. Statusline is redrawn only if title changed,
+    /// and nothing is sent when the same title was set last time
     pub async fn update_buffer_title(
         &mut self,
-        buf: &Buffer<IoWrite>,
+        buf: &N::Buffer,
         buf_title: &str
     ) {
-        let bn = buf
-            .get_number()
-            .await;
-        log::trace!(target: "update title", "{bn:?} => {buf_title}");
+        let unchanged = self.last_buffer_title
+            .as_ref()
+            .map_or(false, |(b, t)| b == buf.get_value() && t == buf_title);
+        if unchanged {
+            log::trace!(target: "update title", "{:?} is unchanged", buf.get_value());
 
-        let numbered_names = (1..99)
-            .map(|attempt_nr| format!("{buf_title}({attempt_nr})"));
+            return
+        }
 
-        for name in std::iter::once(buf_title.to_string())
-            .chain(numbered_names)
-        {
-            if let Err(e) = buf
-                .set_name(&name)
-                .await
-            {
-                log::trace!(
-                    target: "update title",
-                    "{bn:?} => {buf_title}: {:?}", e.to_string()
-                );
+        log::trace!(target: "update title", "{:?} => {buf_title}", buf.get_value());
+
+        let cmd = indoc! {"
+            local buf, title = ...
+            local prev_name = vim.api.nvim_buf_get_name(buf)
+            if prev_name == vim.fn.fnamemodify(title, ':p') then
+                return
+            end
+            local taken = {}
+            for _, b in ipairs(vim.api.nvim_list_bufs()) do
+                if b ~= buf then
+                    taken[vim.api.nvim_buf_get_name(b)] = true
+                end
+            end
+            local name, attempt_nr = title, 0
+            while taken[vim.fn.fnamemodify(name, ':p')] do
+                attempt_nr = attempt_nr + 1
+                name = title .. '(' .. attempt_nr .. ')'
+            end
+            vim.api.nvim_buf_set_name(buf, name)
+            if vim.api.nvim_buf_get_name(buf) ~= prev_name then
+                vim.cmd 'redrawstatus'
+            end
+        "};
 
-                use CallError::NeovimError;
-                match *e {
-                    NeovimError(_, m) if m == "Failed to rename buffer" => {}
-                    _ => {
-                        log::error!(target: "update title", "Cannot update title: {e}");
+        let args = vec![buf.get_value().clone(), Value::from(buf_title)];
 
-                        return
-                    }
-                }
-            } else {
-                self.nvim
-                    .command("redraw!")  // To update statusline
-                    .await
-                    .expect("Cannot redraw");
+        if let Err(e) = self.nvim
+            .exec_lua(cmd, args)
+            .await
+        {
+            log::error!(target: "update title", "Cannot update title: {e}");
 
-                return
-            }
+            return
         }
 
-        log::error!(target: "update title", "Max attempts to rename buffer reached");
+        self.last_buffer_title = Some((buf.get_value().clone(), String::from(buf_title)));
     }
 
 
     pub async fn prepare_output_buffer(
         &mut self,
         initial_buf_nr: i64,
+        initial_win_id: i64,
         cmds: OutputCommands
     ) {
         let OutputCommands {
+            context,
             ft,
             edit,
             notify_closed,
             pre,
             cmd_provided_by_user,
             lua_provided_by_user,
-            after
+            after,
+            background_buf_nr,
+            scrollback,
         } = cmds;
 
+        let redraw = if self.quiet { "" } else { "vim.cmd 'redraw'" };
+
         let options = formatdoc! {r#"
             vim.b.page_alternate_bufnr = {initial_buf_nr}
+            vim.b.page_alternate_winid = {initial_win_id}
+            {context}
             if vim.wo.scrolloff > 999 or vim.wo.scrolloff < 0 then
                 vim.g.page_scrolloff_backup = 0
             else
                 vim.g.page_scrolloff_backup = vim.wo.scrolloff
             end
-            vim.bo.scrollback, vim.wo.scrolloff, vim.wo.signcolumn, vim.wo.number =
-                100000, 999, 'no', false
+            {scrollback}
+            vim.wo.scrolloff, vim.wo.signcolumn, vim.wo.number = 999, 'no', false
             {ft}
             {edit}
             vim.api.nvim_create_autocmd('BufEnter', {{
@@ -390,11 +716,21 @@ impl Actions {
             vim.api.nvim_exec_autocmds('User', {{
                 pattern = 'PageOpen'
             }})
-            vim.cmd 'redraw'
+            {redraw}
             {lua_provided_by_user}
             {cmd_provided_by_user}
             {after}
         "#};
+
+        // Unfocused buffer is prepared as if it were current
+        let options = match background_buf_nr {
+            Some(bn) => formatdoc! {"
+                vim.api.nvim_buf_call({bn}, function()
+                {options}
+                end)
+            "},
+            None => options,
+        };
         log::trace!(target: "prepare output", "{options}");
 
         if let Err(e) = self.nvim
@@ -409,6 +745,60 @@ impl Actions {
     }
 
 
+    pub async fn eval_into_buffer_var(
+        &mut self,
+        expr: &str,
+        var: &str,
+        buf: &N::Buffer
+    ) {
+        log::trace!(target: "eval", "b:{var} = {expr}");
+
+        let val = match self.nvim
+            .eval(expr)
+            .await
+        {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!(target: "eval", "Cannot evaluate '{expr}': {e}");
+
+                return
+            }
+        };
+
+        if let Err(e) = buf.set_var(var, val).await {
+            log::error!(target: "eval", "Cannot set b:{var}: {e}");
+        }
+    }
+
+
+    pub async fn call_function_on_buffer(
+        &mut self,
+        func_name: &str,
+        page_id: u128,
+        buf: &N::Buffer
+    ) {
+        log::trace!(target: "call function", "{func_name}");
+
+        let bn = match buf.get_number().await {
+            Ok(bn) => bn,
+            Err(e) => {
+                log::error!(target: "call function", "Cannot get buffer number: {e}");
+                return
+            }
+        };
+
+        // Page id doesn't fit into integer, so it's passed as string
+        let args = vec![Value::from(page_id.to_string()), Value::from(bn)];
+
+        if let Err(e) = self.nvim
+            .call_function(func_name, args)
+            .await
+        {
+            log::error!(target: "call function", "Cannot call function '{func_name}': {e}");
+        }
+    }
+
+
     pub async fn execute_connect_autocmd_on_current_buffer(&mut self) {
         log::trace!(target: "au PageConnect", "");
 
@@ -455,11 +845,29 @@ impl Actions {
     }
 
 
-    pub async fn execute_command_post_lua(&self, lua_expr: &str) {
+    pub async fn execute_command_post_lua(
+        &self,
+        lua_expr: &str,
+        page_id: u128,
+        buf: &N::Buffer
+    ) {
         log::trace!(target: "command post lua", "{lua_expr}");
 
+        let bn = match buf.get_number().await {
+            Ok(bn) => bn,
+            Err(e) => {
+                log::error!(target: "command post lua", "Cannot get buffer number: {e}");
+                return
+            }
+        };
+
+        let page_ctx = Value::Map(vec![
+            (Value::from("page_id"), Value::from(page_id.to_string())),
+            (Value::from("buf"), Value::from(bn)),
+        ]);
+
         if let Err(e) = self.nvim
-            .exec_lua(lua_expr, vec![])
+            .exec_lua(lua_expr, vec![page_ctx])
             .await
         {
             log::error!(
@@ -472,7 +880,7 @@ impl Actions {
 
     pub async fn switch_to_window_and_buffer(
         &mut self,
-        (win, buf): &(Window<IoWrite>, Buffer<IoWrite>)
+        (win, buf): &(N::Window, N::Buffer)
     ) {
         let wn = win
             .get_number()
@@ -500,7 +908,7 @@ impl Actions {
 
     pub async fn switch_to_buffer(
         &mut self,
-        buf: &Buffer<IoWrite>
+        buf: &N::Buffer
     ) -> Result<(), Box<CallError>> {
         log::trace!(target: "set buffer", "{:?}", buf.get_value());
 
@@ -562,49 +970,410 @@ impl Actions {
     }
 
 
-    pub async fn open_file_buffer(
+    /// Opens each file in a way selected by --open-files and prepares
+    /// it with `cmds`. Split windows get size and direction of split
+    /// flags if they're provided. Files that cannot be opened are
+    /// returned with their errors, so that they don't abort the rest
+    pub async fn open_file_buffers(
         &mut self,
-        file_opt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        log::trace!(target: "open file", "{file_opt:?}");
+        files: &[crate::cli::FileOption],
+        mode: crate::cli::FilesOpenMode,
+        split: &crate::cli::SplitOptions,
+        (initial_buf_nr, initial_win_id): (i64, i64),
+        cmds: &OutputCommands,
+        follow: bool
+    ) -> Vec<(String, Box<dyn std::error::Error>)> {
+        let mut failed = vec![];
+
+        for f in files {
+            if let Err(e) = self.open_file(f, mode, split).await {
+                failed.push((String::from(f.as_str()), e));
+
+                continue
+            }
 
-        self.nvim
-            .command(&format!("e {}", file_opt))
-            .await?;
+            self.prepare_output_buffer(initial_buf_nr, initial_win_id, cmds.clone())
+                .await;
 
-        Ok(())
+            if follow {
+                self.set_current_buffer_follow_output_mode()
+                    .await;
+            } else {
+                self.set_current_buffer_scroll_mode()
+                    .await;
+            }
+        }
+
+        failed
     }
 
 
-    pub async fn notify_query_finished(&mut self, lines_read_count: usize) {
-        log::trace!(target: "query finished", "Read {lines_read_count} lines");
+    async fn open_file(
+        &mut self,
+        f: &crate::cli::FileOption,
+        mode: crate::cli::FilesOpenMode,
+        split: &crate::cli::SplitOptions
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use crate::cli::{FileOption, FilesOpenMode};
 
-        let cmd = formatdoc! {"
-            vim.cmd 'redraw'
-            local msg = '-- [PAGE] {lines_read_count} lines read; has more --'
-            vim.api.nvim_echo({{ {{ msg, 'Comment', }}, }}, false, {{}})
-        "};
+        let split_cmd = split_window_cmd(split)
+            .filter(|_| matches!(mode, FilesOpenMode::Splits));
 
-        self.nvim
-            .exec_lua(&cmd, vec![])
-            .await
-            .expect("Cannot notify query finished");
-    }
+        // File is opened in place of split window's buffer
+        let mode = match &split_cmd {
+            Some(split_cmd) => {
+                self.nvim
+                    .exec_lua(split_cmd, vec![])
+                    .await?;
 
+                FilesOpenMode::Buffers
+            }
+            None => mode,
+        };
 
-    pub async fn notify_end_of_input(&mut self) {
-        log::trace!(target: "end input", "");
+        let opened = if let FileOption::PathAt { path, line, col } = f {
+            self.open_file_buffer_at(path, Some(*line), *col, mode)
+                .await
+        } else {
+            self.open_file_buffer(f.as_str(), mode)
+                .await
+        };
+
+        if opened.is_err() && split_cmd.is_some() {
+            if let Err(e) = self.nvim.command("close").await {
+                log::error!(target: "open file", "Cannot close split window: {e}");
+            }
+        }
+
+        opened
+    }
+
+
+    pub async fn open_file_buffer(
+        &mut self,
+        file_opt: &str,
+        mode: crate::cli::FilesOpenMode,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        log::trace!(target: "open file", "{file_opt:?} {mode:?}");
+
+        let edit = files_open_command(mode);
+        self.nvim
+            .command(&format!("{edit} {}", file_opt))
+            .await?;
+
+        Ok(())
+    }
+
+
+    /// Opens file and moves cursor to provided line and column.
+    /// Line that exceeds file length is clamped to the last line
+    pub async fn open_file_buffer_at(
+        &mut self,
+        file_path: &str,
+        line: Option<u64>,
+        col: Option<u64>,
+        mode: crate::cli::FilesOpenMode,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        log::trace!(target: "open file", "{file_path:?} at {line:?}:{col:?} {mode:?}");
+
+        let Some(line) = line else {
+            return self
+                .open_file_buffer(file_path, mode)
+                .await
+        };
+
+        let edit = files_open_command(mode);
+        self.nvim
+            .command(&format!("{edit} +{line} {file_path}"))
+            .await?;
 
         let cmd = indoc! {"
+            local line, col = ...
+            line = math.min(line, vim.api.nvim_buf_line_count(0))
+            vim.fn.cursor(line, col)
+        "};
+        let args = vec![Value::from(line), Value::from(col.unwrap_or(1))];
+
+        self.nvim
+            .exec_lua(cmd, args)
+            .await?;
+
+        Ok(())
+    }
+
+
+    pub async fn set_buffer_filetype(&mut self, buf: &N::Buffer, filetype: &str) {
+        log::trace!(target: "set filetype", "{filetype}");
+
+        if let Err(e) = buf.set_option("filetype", Value::from(filetype)).await {
+            log::error!(target: "set filetype", "Error when setting filetype: {e}");
+        }
+    }
+
+
+    /// Formats current time with neovim's strftime(),
+    /// since page has no dependency to do that itself
+    pub async fn format_current_time(&mut self, format: &str) -> String {
+        let time = self.nvim
+            .call_function("strftime", vec![Value::from(format)])
+            .await;
+
+        match time {
+            Ok(Value::String(s)) => s
+                .into_str()
+                .unwrap_or_default(),
+
+            Ok(v) => {
+                log::error!(target: "format time", "Unexpected strftime result: {v}");
+                String::new()
+            },
+
+            Err(e) => {
+                log::error!(target: "format time", "Cannot format time: {e}");
+                String::new()
+            },
+        }
+    }
+
+
+    /// Echoes that queried part was read, message might be
+    /// overridden by template with {lines} or suppressed by empty one
+    pub async fn notify_query_finished(
+        &mut self,
+        lines_read_count: usize,
+        template: Option<&str>
+    ) -> Result<(), Error> {
+        log::trace!(target: "query finished", "Read {lines_read_count} lines");
+
+        let msg = template
+            .unwrap_or("-- [PAGE] {lines} lines read; has more --")
+            .replace("{lines}", &lines_read_count.to_string());
+
+        self.echo_notification(&msg)
+            .await
+    }
+
+
+    /// Echoes that input ended, message might be
+    /// overridden by template or suppressed by empty one
+    pub async fn notify_end_of_input(&mut self, template: Option<&str>) -> Result<(), Error> {
+        log::trace!(target: "end input", "");
+
+        let msg = template
+            .unwrap_or("-- [PAGE] end of input --");
+
+        self.echo_notification(msg)
+            .await
+    }
+
+
+    /// Message is passed as argument rather than interpolated
+    /// into lua, so it can't break the command
+    async fn echo_notification(&mut self, msg: &str) -> Result<(), Error> {
+        if self.quiet {
+            return Ok(())
+        }
+
+        let cmd = indoc! {"
+            local msg = ...
             vim.cmd 'redraw'
-            local msg = '-- [PAGE] end of input --'
-            vim.api.nvim_echo({{ msg, 'Comment' }, }, false, {})
+            if msg ~= '' then
+                vim.api.nvim_echo({{ msg, 'Comment' }, }, false, {})
+            end
         "};
 
         self.nvim
+            .exec_lua(cmd, vec![Value::from(msg)])
+            .await?;
+
+        Ok(())
+    }
+
+
+    /// Echoes how much was read, styled like other notifications
+    pub async fn notify_progress(&mut self, bytes: u64, lines: u64) {
+        log::trace!(target: "progress", "{lines} lines / {bytes} bytes");
+
+        if self.quiet {
+            return
+        }
+
+        let cmd = formatdoc! {"
+            local msg = '-- [PAGE] {lines} lines / {bytes} bytes --'
+            vim.api.nvim_echo({{ {{ msg, 'Comment', }}, }}, false, {{}})
+        "};
+
+        if let Err(e) = self.nvim
+            .exec_lua(&cmd, vec![])
+            .await
+        {
+            log::error!(target: "progress", "Cannot notify progress: {e}");
+        }
+    }
+
+
+    /// Updates count of lines read displayed by --winbar
+    pub async fn update_winbar_lines_read(&mut self, buf: &N::Buffer, lines_read: usize) {
+        log::trace!(target: "update winbar", "{lines_read}");
+
+        let cmd = indoc! {"
+            local buf, lines_read = ...
+            vim.api.nvim_buf_set_var(buf, 'page_lines_read', lines_read)
+            vim.cmd 'redrawstatus!'
+        "};
+        let args = vec![buf.get_value().clone(), Value::from(lines_read as u64)];
+
+        if let Err(e) = self.nvim
+            .exec_lua(cmd, args)
+            .await
+        {
+            log::error!(target: "update winbar", "Cannot update winbar: {e}");
+        }
+    }
+
+
+    /// Rings bell on terminal where neovim is displayed
+    pub async fn ring_bell(&mut self) {
+        log::trace!(target: "ring bell", "");
+
+        let cmd = "vim.fn.chansend(vim.v.stderr, \"\\a\")";
+
+        if let Err(e) = self.nvim
             .exec_lua(cmd, vec![])
             .await
-            .expect("Cannot notify end of input");
+        {
+            log::error!(target: "ring bell", "Cannot ring bell: {e}");
+        }
+    }
+
+
+    /// Closes floating window which displays `buf` when focus leaves it.
+    /// Leaving any non-floating window that displays `buf`
+    /// (e.g. on PageDisconnect) is ignored
+    pub async fn close_floating_window_on_leave(&mut self, buf: &N::Buffer) {
+        let bn = match buf.get_number().await {
+            Ok(bn) => bn,
+            Err(e) => {
+                log::error!(target: "float autoclose", "Cannot get buffer number: {e}");
+                return
+            }
+        };
+
+        let cmd = formatdoc! {"
+            vim.api.nvim_create_autocmd({{ 'WinLeave', 'BufLeave' }}, {{
+                buffer = {bn},
+                callback = function()
+                    local win = vim.api.nvim_get_current_win()
+                    if vim.api.nvim_win_get_config(win).relative == '' then
+                        return
+                    end
+                    vim.schedule(function()
+                        pcall(vim.api.nvim_win_close, win, false)
+                    end)
+                    return true
+                end,
+            }})
+        "};
+        log::trace!(target: "float autoclose", "{cmd}");
+
+        if let Err(e) = self.nvim.exec_lua(&cmd, vec![]).await {
+            log::error!(target: "float autoclose", "Cannot set autoclose: {e}");
+        }
+    }
+
+
+    /// Resizes floating window which displays `buf` to fit `lines_count`
+    /// lines, clamped by `max_height` and editor bounds.
+    /// Returns true when window can't grow any further
+    pub async fn fit_floating_window_to_content(
+        &mut self,
+        buf: &N::Buffer,
+        lines_count: usize,
+        max_height: Option<u16>
+    ) -> bool {
+        let cmd = indoc! {"
+            local buf, lines_count, max_height = ...
+            local win = vim.fn.bufwinid(buf)
+            if win == -1 then
+                return true
+            end
+            local config = vim.api.nvim_win_get_config(win)
+            if config.relative == '' then
+                return true
+            end
+            local eh = vim.o.lines - vim.o.cmdheight
+            local row = vim.api.nvim_win_get_position(win)[1]
+            local border = config.border and 2 or 0
+            local max = math.max(1, math.min(max_height or eh, eh - row - border))
+            local height = math.max(1, math.min(lines_count, max))
+            if height ~= vim.api.nvim_win_get_height(win) then
+                vim.api.nvim_win_set_config(win, { height = height })
+            end
+            return height == max
+        "};
+        let args = vec![
+            buf.get_value().clone(),
+            Value::from(lines_count),
+            max_height.map_or(Value::Nil, Value::from),
+        ];
+        log::trace!(target: "fit float", "{cmd}");
+
+        self.nvim
+            .exec_lua(cmd, args)
+            .await
+            .map(|v| v.as_bool().unwrap_or(true))
+            .unwrap_or_else(|e| {
+                log::error!(target: "fit float", "Cannot resize floating window: {e}");
+                true
+            })
+    }
+
+
+    /// Returns writer into terminal channel of output buffer
+    /// created with --backend=channel
+    pub fn open_channel_sink(&self, chan: i64, reconnect: Option<ReconnectFn<N>>) -> ChannelSink {
+        ChannelSink::open(self.nvim.clone(), chan, reconnect)
+    }
+
+
+    /// Returns writer into scratch output buffer
+    /// created with --backend=buffer
+    pub fn open_buffer_sink(
+        &self,
+        buf_nr: i64,
+        follow: bool,
+        strip_ansi: bool,
+        ansi_highlight: bool,
+        reconnect: Option<ReconnectFn<N>>
+    ) -> BufferSink {
+        BufferSink::open(self.nvim.clone(), buf_nr, follow, strip_ansi, ansi_highlight, reconnect)
+    }
+
+
+    /// Makes output buffer notify page through channel
+    /// of connection that replaced the lost one
+    pub async fn set_notification_channel(&mut self, buf: &N::Buffer, channel: u64) {
+        if let Err(e) = buf.set_var("page_channel", Value::from(channel)).await {
+            log::error!(target: "reconnect", "Cannot set notification channel: {e}");
+        }
+    }
+
+
+    /// Removes lines of scratch output buffer created with
+    /// --backend=buffer, so that next write replaces them
+    pub async fn clear_buffer_sink(&mut self, buf_nr: i64) {
+        let cmd = formatdoc! {"
+            local modifiable = vim.api.nvim_buf_get_option({buf_nr}, 'modifiable')
+            vim.api.nvim_buf_set_option({buf_nr}, 'modifiable', true)
+            vim.api.nvim_buf_set_lines({buf_nr}, 0, -1, false, {{}})
+            vim.api.nvim_buf_clear_namespace({buf_nr}, vim.api.nvim_create_namespace('page_ansi'), 0, -1)
+            vim.api.nvim_buf_set_option({buf_nr}, 'modifiable', modifiable)
+        "};
+        log::trace!(target: "clear buffer", "{cmd}");
+
+        if let Err(e) = self.nvim.exec_lua(&cmd, vec![]).await {
+            log::error!(target: "clear buffer", "Cannot clear output buffer: {e}");
+        }
     }
 
 
@@ -620,60 +1389,760 @@ impl Actions {
 
         log::trace!(target: "get var", "Key '{key}': '{val:?}'");
 
-        val.unwrap_or_else(|e| {
-            use CallError::NeovimError;
-            match *e {
-                NeovimError(_, m) if m == format!("Key not found: {key}") => {},
-
-                _ => {
-                    log::error!(
-                        target: "get var",
-                        "Error getting var: {key}, {e}"
-                    );
-                }
-            }
+        val.unwrap_or_else(|e| {
+            if !is_key_not_found(&e, key) {
+                log::error!(
+                    target: "get var",
+                    "Error getting var: {key}, {e}"
+                );
+            }
+
+            String::from(default)
+        })
+    }
+}
+
+
+/// Neovim API methods used by `Actions`. It's implemented
+/// for `nvim_rs::Neovim` and by mock that records calls in tests
+#[async_trait::async_trait]
+pub trait NeovimApi: Clone + Send + Sync + 'static {
+    type Buffer: NeovimBufferApi;
+    type Window: NeovimWindowApi;
+
+    /// Returns buffer by handle received from neovim
+    fn buffer(&self, handle: Value) -> Self::Buffer;
+
+    async fn exec_lua(&self, code: &str, args: Vec<Value>) -> Result<Value, Box<CallError>>;
+    async fn command(&self, command: &str) -> Result<(), Box<CallError>>;
+    async fn eval(&self, expr: &str) -> Result<Value, Box<CallError>>;
+    async fn call_function(&self, fname: &str, args: Vec<Value>) -> Result<Value, Box<CallError>>;
+    async fn get_var(&self, name: &str) -> Result<Value, Box<CallError>>;
+    async fn get_current_buf(&self) -> Result<Self::Buffer, Box<CallError>>;
+    async fn set_current_buf(&self, buffer: &Self::Buffer) -> Result<(), Box<CallError>>;
+    async fn set_current_win(&self, window: &Self::Window) -> Result<(), Box<CallError>>;
+    async fn chan_send(&self, chan: i64, data: &str) -> Result<(), Box<CallError>>;
+}
+
+#[async_trait::async_trait]
+pub trait NeovimBufferApi: Clone + Send + Sync + 'static {
+    fn get_value(&self) -> &Value;
+
+    async fn get_number(&self) -> Result<i64, Box<CallError>>;
+    async fn set_var(&self, name: &str, value: Value) -> Result<(), Box<CallError>>;
+    async fn set_option(&self, name: &str, value: Value) -> Result<(), Box<CallError>>;
+}
+
+#[async_trait::async_trait]
+pub trait NeovimWindowApi: Clone + Send + Sync + 'static {
+    async fn get_number(&self) -> Result<i64, Box<CallError>>;
+}
+
+
+#[async_trait::async_trait]
+impl NeovimApi for Neovim<IoWrite> {
+    type Buffer = Buffer<IoWrite>;
+    type Window = Window<IoWrite>;
+
+    fn buffer(&self, handle: Value) -> Self::Buffer {
+        Buffer::new(handle, self.clone())
+    }
+
+    async fn exec_lua(&self, code: &str, args: Vec<Value>) -> Result<Value, Box<CallError>> {
+        Neovim::exec_lua(self, code, args).await
+    }
+
+    async fn command(&self, command: &str) -> Result<(), Box<CallError>> {
+        Neovim::command(self, command).await
+    }
+
+    async fn eval(&self, expr: &str) -> Result<Value, Box<CallError>> {
+        Neovim::eval(self, expr).await
+    }
+
+    async fn call_function(&self, fname: &str, args: Vec<Value>) -> Result<Value, Box<CallError>> {
+        Neovim::call_function(self, fname, args).await
+    }
+
+    async fn get_var(&self, name: &str) -> Result<Value, Box<CallError>> {
+        Neovim::get_var(self, name).await
+    }
+
+    async fn get_current_buf(&self) -> Result<Self::Buffer, Box<CallError>> {
+        Neovim::get_current_buf(self).await
+    }
+
+    async fn set_current_buf(&self, buffer: &Self::Buffer) -> Result<(), Box<CallError>> {
+        Neovim::set_current_buf(self, buffer).await
+    }
+
+    async fn set_current_win(&self, window: &Self::Window) -> Result<(), Box<CallError>> {
+        Neovim::set_current_win(self, window).await
+    }
+
+    async fn chan_send(&self, chan: i64, data: &str) -> Result<(), Box<CallError>> {
+        Neovim::chan_send(self, chan, data).await
+    }
+}
+
+#[async_trait::async_trait]
+impl NeovimBufferApi for Buffer<IoWrite> {
+    fn get_value(&self) -> &Value {
+        Buffer::get_value(self)
+    }
+
+    async fn get_number(&self) -> Result<i64, Box<CallError>> {
+        Buffer::get_number(self).await
+    }
+
+    async fn set_var(&self, name: &str, value: Value) -> Result<(), Box<CallError>> {
+        Buffer::set_var(self, name, value).await
+    }
+
+    async fn set_option(&self, name: &str, value: Value) -> Result<(), Box<CallError>> {
+        Buffer::set_option(self, name, value).await
+    }
+}
+
+#[async_trait::async_trait]
+impl NeovimWindowApi for Window<IoWrite> {
+    async fn get_number(&self) -> Result<i64, Box<CallError>> {
+        Window::get_number(self).await
+    }
+}
+
+
+/// Checks whether error is returned for missing variable.
+/// Neovim doesn't provide structured error for that, so messages
+/// of different versions are matched: `Key not found: <key>` and
+/// older `Key '<key>' not found`
+fn is_key_not_found(e: &CallError, key: &str) -> bool {
+    let CallError::NeovimError(_, m) = e else {
+        return false
+    };
+
+    m == &format!("Key not found: {key}") ||
+        m.ends_with(&format!("Key '{key}' not found"))
+}
+
+
+/// Errors returned from actions which failure might be recovered from
+#[derive(Debug)]
+pub enum Error {
+    /// Neovim returned error or connection with it is lost
+    Call(Box<CallError>),
+    /// Neovim responded with unexpected value
+    Response(String),
+}
+
+impl From<Box<CallError>> for Error {
+    fn from(e: Box<CallError>) -> Self {
+        Error::Call(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Call(e) => write!(f, "{e}"),
+            Error::Response(e) => write!(f, "Unexpected response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+
+/// Opens split window by -r -l -u -d -R -L -U -D flags and makes it current,
+/// returns None if no split direction is provided
+fn split_window_cmd(opt: &crate::cli::SplitOptions) -> Option<String> {
+    let w_ratio = |s| format!("' .. tostring(math.floor(((w / 2) * 3) / {})) .. '", s + 1);
+    let h_ratio = |s| format!("' .. tostring(math.floor(((h / 2) * 3) / {})) .. '", s + 1);
+
+    let (a, b) = ("aboveleft", "belowright");
+    let (w, h) = ("winfixwidth", "winfixheight");
+    let (v, z) = ("vsplit", "split");
+
+    let w_equal = "' .. tostring(math.floor(vim.o.columns / n)) .. '".to_string();
+    let h_equal = "' .. tostring(math.floor((vim.o.lines - vim.o.cmdheight) / n)) .. '".to_string();
+
+    let (direction, size, split, fix);
+
+    if opt.split_equal && opt.split_right != 0 {
+        (direction = b, size = w_equal, split = v, fix = w)
+
+    } else if opt.split_equal && opt.split_left != 0 {
+        (direction = a, size = w_equal, split = v, fix = w)
+
+    } else if opt.split_equal && opt.split_below != 0 {
+        (direction = b, size = h_equal, split = z, fix = h)
+
+    } else if opt.split_equal && opt.split_above != 0 {
+        (direction = a, size = h_equal, split = z, fix = h)
+
+    } else if opt.split_right != 0 {
+        (direction = b, size = w_ratio(opt.split_right), split = v, fix = w)
+
+    } else if opt.split_left != 0 {
+        (direction = a,  size = w_ratio(opt.split_left), split = v, fix = w)
+
+    } else if opt.split_below != 0 {
+        (direction = b, size = h_ratio(opt.split_below), split = z, fix = h)
+
+    } else if opt.split_above != 0 {
+        (direction = a, size = h_ratio(opt.split_above), split = z, fix = h)
+
+    } else if let Some(split_right_cols) = opt.split_right_cols.map(|x| x.to_string()) {
+        (direction = b, size = split_right_cols, split = v, fix = w)
+
+    } else if let Some(split_left_cols) = opt.split_left_cols.map(|x| x.to_string()) {
+        (direction = a, size = split_left_cols,  split = v, fix = w)
+
+    } else if let Some(split_below_rows) = opt.split_below_rows.map(|x| x.to_string()) {
+        (direction = b, size = split_below_rows, split = z, fix = h)
+
+    } else if let Some(split_above_rows) = opt.split_above_rows.map(|x| x.to_string()) {
+        (direction = a, size = split_above_rows, split = z, fix = h)
+
+    } else {
+        return None
+    };
+
+    let cmd = formatdoc! {"
+        local prev_win = vim.api.nvim_get_current_win()
+        local w = vim.api.nvim_win_get_width(prev_win)
+        local h = vim.api.nvim_win_get_height(prev_win)
+        local n = #vim.tbl_filter(function(win)
+            return vim.api.nvim_win_get_config(win).relative == ''
+        end, vim.api.nvim_tabpage_list_wins(0)) + 1
+        vim.cmd('{direction} {size}{split}')
+        local win = vim.api.nvim_get_current_win()
+        vim.api.nvim_win_set_option(win, '{fix}', true)
+    "};
+
+    Some(cmd)
+}
+
+
+/// Focuses window with `buf` if there's one, otherwise displays `buf`
+/// in current window or in split window opened by `split` flags
+/// (below current one if there's no split direction)
+fn focus_buffer_cmd(split: Option<&crate::cli::SplitOptions>) -> String {
+    let display_cmd = match split {
+        Some(opt) => {
+            let split_cmd = split_window_cmd(opt)
+                .unwrap_or_else(|| String::from(indoc! {"
+                    vim.cmd 'belowright split'
+                    vim.api.nvim_win_set_option(0, 'winfixheight', true)
+                "}));
+
+            formatdoc! {"
+                {split_cmd}
+                vim.api.nvim_set_current_buf(buf)
+            "}
+        }
+        None => String::from("vim.api.nvim_set_current_buf(buf)"),
+    };
+
+    formatdoc! {"
+        local active_buf = vim.api.nvim_get_current_buf()
+        if active_buf == buf then
+            return
+        end
+        for _, win in ipairs(vim.api.nvim_list_wins()) do
+            local win_buf = vim.api.nvim_win_get_buf(win)
+            if win_buf == buf then
+                vim.api.nvim_set_current_win(win)
+                return
+            end
+        end
+        {display_cmd}
+    "}
+}
+
+
+/// Prefix of `channel:<ID>` which is used instead of PTY path
+/// for output buffers created with --backend=channel
+pub const CHANNEL_SINK_PREFIX: &str = "channel:";
+
+
+/// Prefix of `buffer:<NUMBER>` which is used instead of PTY path
+/// for output buffers created with --backend=buffer
+pub const BUFFER_SINK_PREFIX: &str = "buffer:";
+
+
+/// Where text of output buffer is written. It's stored in instance
+/// variable and printed with -p in its string form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    Pty(PathBuf),
+    Channel(i64),
+    Buffer(i64),
+}
+
+impl SinkTarget {
+    /// Parses `channel:<ID>`, `buffer:<NUMBER>`,
+    /// anything else is considered PTY path
+    pub fn parse(s: &str) -> SinkTarget {
+        let parse_id = |prefix| s
+            .strip_prefix(prefix)
+            .and_then(|id: &str| id.parse().ok());
+
+        if let Some(chan) = parse_id(CHANNEL_SINK_PREFIX) {
+            SinkTarget::Channel(chan)
+        } else if let Some(buf_nr) = parse_id(BUFFER_SINK_PREFIX) {
+            SinkTarget::Buffer(buf_nr)
+        } else {
+            SinkTarget::Pty(PathBuf::from(s))
+        }
+    }
+
+    pub fn is_pty(&self) -> bool {
+        matches!(self, SinkTarget::Pty(_))
+    }
+}
+
+impl std::fmt::Display for SinkTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkTarget::Pty(pty_path) => write!(f, "{}", pty_path.display()),
+            SinkTarget::Channel(chan) => write!(f, "{CHANNEL_SINK_PREFIX}{chan}"),
+            SinkTarget::Buffer(buf_nr) => write!(f, "{BUFFER_SINK_PREFIX}{buf_nr}"),
+        }
+    }
+}
+
+
+/// Writes text into terminal opened by `nvim_open_term()`.
+/// It's used instead of PTY device with --backend=channel
+pub struct ChannelSink {
+    queue: RpcQueue<Vec<u8>>,
+}
+
+impl ChannelSink {
+    fn open<N: NeovimApi>(nvim: N, chan: i64, reconnect: Option<ReconnectFn<N>>) -> ChannelSink {
+        let send = move |nvim: N, data: Vec<u8>| {
+            // There's no line discipline that translates newlines
+            let data = String::from_utf8_lossy(&data)
+                .replace('\n', "\r\n");
+
+            Box::pin(async move {
+                nvim.chan_send(chan, &data)
+                    .await
+            }) as std::pin::Pin<Box<dyn std::future::Future<Output = _> + Send>>
+        };
+
+        let queue = RpcQueue::spawn(|rx| {
+            send_joined(rx, nvim, Vec::extend, send, reconnect, "Cannot send to channel")
+        });
+
+        ChannelSink { queue }
+    }
+}
+
+impl std::io::Write for ChannelSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.queue.push(buf.to_vec())?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ChannelSink {
+    fn drop(&mut self) {
+        self.queue
+            .close();
+    }
+}
+
+
+/// Queue of writes that are sent to neovim by background task,
+/// so that writing doesn't wait for RPC round-trip and writes
+/// queued in the meantime are sent together
+struct RpcQueue<T> {
+    tx: Option<tokio::sync::mpsc::UnboundedSender<T>>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl<T> RpcQueue<T> {
+    fn spawn<F>(send_all: impl FnOnce(tokio::sync::mpsc::UnboundedReceiver<T>) -> F) -> Self
+    where
+        F: std::future::Future<Output = ()> + Send + 'static
+    {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::task::spawn(send_all(rx));
+
+        RpcQueue { tx: Some(tx), task: Some(task) }
+    }
+
+    /// Fails once background task stopped after failed request
+    fn push(&self, item: T) -> std::io::Result<()> {
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(item).ok())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "neovim doesn't accept output"))
+    }
+
+    /// Waits until everything queued is sent
+    fn close(&mut self) {
+        self.tx
+            .take();
+
+        if let Some(task) = self.task.take() {
+            let _ = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current()
+                    .block_on(task)
+            });
+        }
+    }
+}
+
+/// Connects again to neovim once connection is lost (see --reconnect),
+/// resolves to `None` if it cannot be done
+pub type ReconnectFn<N> = std::sync::Arc<
+    dyn Fn() -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<N>> + Send>> + Send + Sync
+>;
+
+/// Receives queued items, joins items that are already available
+/// and sends them with a single request until sending fails.
+/// If connection was lost and `reconnect` is provided then
+/// failed request is sent again once through the new connection
+async fn send_joined<N: NeovimApi, T: Clone>(
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<T>,
+    mut nvim: N,
+    join: impl Fn(&mut T, T),
+    send: impl Fn(N, T) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), Box<CallError>>> + Send>>,
+    reconnect: Option<ReconnectFn<N>>,
+    target: &str
+) {
+    while let Some(mut joined) = rx.recv().await {
+        while let Ok(next) = rx.try_recv() {
+            join(&mut joined, next);
+        }
+
+        let retried = reconnect
+            .as_ref()
+            .map(|_| joined.clone());
+
+        let Err(e) = send(nvim.clone(), joined).await else {
+            continue
+        };
+        log::info!(target: "sink", "{target}: {e}");
+
+        let (Some(reconnect), Some(retried)) = (&reconnect, retried) else {
+            return
+        };
+
+        if !is_connection_lost(&e) {
+            return
+        }
+
+        let Some(reconnected) = reconnect().await else {
+            return
+        };
+        nvim = reconnected;
+
+        if let Err(e) = send(nvim.clone(), retried).await {
+            log::info!(target: "sink", "{target} after reconnect: {e}");
+
+            return
+        }
+    }
+}
+
+/// Request wasn't sent or its response won't be received,
+/// unlike errors returned by neovim itself
+fn is_connection_lost(e: &CallError) -> bool {
+    matches!(e, CallError::SendError(..) | CallError::InternalReceiveError(..))
+}
+
+
+/// Lines queued for appending into scratch buffer with their highlights
+/// as `(line_idx, group, col_start, col_end)`. First lines written
+/// into buffer are marked, so that they're sent the same way if retried
+#[derive(Default, Clone)]
+struct BufferLines {
+    lines: Vec<String>,
+    highlights: Vec<(usize, &'static str, usize, usize)>,
+    first: bool,
+}
+
+impl BufferLines {
+    fn join(&mut self, next: BufferLines) {
+        let offset = self.lines.len();
+        self.highlights.extend(next.highlights
+            .into_iter()
+            .map(|(line_idx, group, start, end)| (line_idx + offset, group, start, end))
+        );
+        self.lines.extend(next.lines);
+    }
+}
+
+
+/// Appends complete lines into scratch buffer opened with --backend=buffer.
+/// Buffer is replaced only if it's pristine, so reused buffer is appended.
+/// Cursor follows appended lines with -f or when it's on the last line
+pub struct BufferSink {
+    pending: Vec<u8>,
+    strip_ansi: bool,
+    ansi_highlight: bool,
+    ansi_style: crate::ansi::Style,
+    pushed: bool,
+    queue: RpcQueue<BufferLines>,
+}
+
+impl BufferSink {
+    fn open<N: NeovimApi>(
+        nvim: N,
+        buf_nr: i64,
+        follow: bool,
+        strip_ansi: bool,
+        ansi_highlight: bool,
+        reconnect: Option<ReconnectFn<N>>
+    ) -> BufferSink {
+        let send = move |nvim: N, lines: BufferLines| {
+            let args = append_lines_args(buf_nr, lines, follow, ansi_highlight);
+
+            Box::pin(async move {
+                nvim.exec_lua(APPEND_LINES_CMD, args)
+                    .await
+                    .map(|_| ())
+            }) as std::pin::Pin<Box<dyn std::future::Future<Output = _> + Send>>
+        };
+
+        let queue = RpcQueue::spawn(|rx| {
+            send_joined(rx, nvim, BufferLines::join, send, reconnect, "Cannot append lines")
+        });
+
+        BufferSink {
+            pending: vec![],
+            strip_ansi: strip_ansi || ansi_highlight,
+            ansi_highlight,
+            ansi_style: crate::ansi::Style::default(),
+            pushed: false,
+            queue,
+        }
+    }
+
+
+    fn push(&mut self, mut queued: BufferLines) -> std::io::Result<()> {
+        queued.first = !self.pushed;
+        self.pushed = true;
+
+        self.queue.push(queued)
+    }
+
+
+    /// Converts line into string, escape sequences are stripped
+    /// from it with --strip-ansi and their colors are collected
+    /// with --ansi-highlight
+    fn push_line(&mut self, ln: &[u8], queued: &mut BufferLines) {
+        if !self.strip_ansi {
+            queued.lines.push(String::from_utf8_lossy(ln).into_owned());
+            return
+        }
+
+        let (text, line_highlights) = crate::ansi::strip(ln, &mut self.ansi_style);
+
+        if self.ansi_highlight {
+            let line_idx = queued.lines.len();
+            queued.highlights.extend(line_highlights
+                .into_iter()
+                .map(|hl| (line_idx, hl.group, hl.start, hl.end))
+            );
+        }
+
+        queued.lines.push(String::from_utf8_lossy(&text).into_owned());
+    }
+}
+
+/// Appends lines, or replaces them if buffer has only an empty line,
+/// and moves cursor of following windows to the last line
+const APPEND_LINES_CMD: &str = indoc! {"
+    local buf, lines, follow, first_write, highlights, groups, bold_group = ...
+    if groups ~= vim.NIL then
+        for i, group in ipairs(groups) do
+            vim.api.nvim_set_hl(0, group[1], { fg = group[2], ctermfg = i - 1, default = true })
+        end
+        vim.api.nvim_set_hl(0, bold_group, { bold = true, default = true })
+    end
+    local last = vim.api.nvim_buf_line_count(buf)
+    local pristine = last == 1 and vim.api.nvim_buf_get_lines(buf, 0, 1, false)[1] == ''
+    local following = {}
+    for _, win in ipairs(vim.fn.win_findbuf(buf)) do
+        local at_last = vim.api.nvim_win_get_cursor(win)[1] == last
+        if (follow and first_write) or (not pristine and at_last) then
+            table.insert(following, win)
+        end
+    end
+    local modifiable = vim.api.nvim_buf_get_option(buf, 'modifiable')
+    vim.api.nvim_buf_set_option(buf, 'modifiable', true)
+    vim.api.nvim_buf_set_lines(buf, pristine and 0 or -1, -1, false, lines)
+    vim.api.nvim_buf_set_option(buf, 'modifiable', modifiable)
+    if #highlights > 0 then
+        local ns = vim.api.nvim_create_namespace('page_ansi')
+        local first = pristine and 0 or last
+        for _, hl in ipairs(highlights) do
+            vim.api.nvim_buf_add_highlight(buf, ns, hl[2], first + hl[1], hl[3], hl[4])
+        end
+    end
+    last = vim.api.nvim_buf_line_count(buf)
+    for _, win in ipairs(following) do
+        vim.api.nvim_win_set_cursor(win, { last, 0 })
+    end
+"};
+
+fn append_lines_args(
+    buf_nr: i64,
+    queued: BufferLines,
+    follow: bool,
+    ansi_highlight: bool
+) -> Vec<Value> {
+    let first_write = queued.first;
+
+    // Highlight groups are defined once, before the first line
+    let groups = if ansi_highlight && first_write {
+        Value::from(crate::ansi::COLOR_GROUPS
+            .iter()
+            .map(|(group, color)| Value::from(vec![Value::from(*group), Value::from(*color)]))
+            .collect::<Vec<_>>())
+    } else {
+        Value::Nil
+    };
+
+    let lines = queued.lines
+        .into_iter()
+        .map(Value::from)
+        .collect::<Vec<_>>();
+    let highlights = queued.highlights
+        .into_iter()
+        .map(|(line_idx, group, start, end)| Value::from(vec![
+            Value::from(line_idx),
+            Value::from(group),
+            Value::from(start),
+            Value::from(end),
+        ]))
+        .collect::<Vec<_>>();
+
+    vec![
+        Value::from(buf_nr),
+        Value::from(lines),
+        Value::from(follow),
+        Value::from(first_write),
+        Value::from(highlights),
+        groups,
+        Value::from(crate::ansi::BOLD_GROUP),
+    ]
+}
+
+impl std::io::Write for BufferSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Ok(buf.len())
+        };
+
+        let complete: Vec<u8> = self.pending
+            .drain(..=end)
+            .collect();
+        let mut queued = BufferLines::default();
+        for ln in complete[..end].split(|&b| b == b'\n') {
+            self.push_line(ln.strip_suffix(b"\r").unwrap_or(ln), &mut queued);
+        }
+
+        self.push(queued)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(())
+        }
+
+        let ln = std::mem::take(&mut self.pending);
+        let mut queued = BufferLines::default();
+        self.push_line(&ln, &mut queued);
+
+        self.push(queued)
+    }
+}
+
+impl Drop for BufferSink {
+    fn drop(&mut self) {
+        if let Err(e) = std::io::Write::flush(self) {
+            log::error!(target: "buffer sink", "Cannot append last line: {e}");
+        }
 
-            String::from(default)
-        })
+        self.queue
+            .close();
     }
 }
 
 
-/// This struct holds output buffer together with path to its PTY
-pub struct OutputBuffer {
-    pub buf: Buffer<IoWrite>,
-    pub pty_path: PathBuf,
+/// This struct holds output buffer together with
+/// PTY, terminal channel or scratch buffer it's written into
+pub struct OutputBuffer<B = Buffer<IoWrite>> {
+    pub buf: B,
+    pub sink: SinkTarget,
 }
 
-impl TryFrom<(Value, &Neovim<IoWrite>)> for OutputBuffer {
+impl<N: NeovimApi> TryFrom<(Value, &N)> for OutputBuffer<N::Buffer> {
     type Error = String;
 
     fn try_from(
-        (val, nvim): (Value, &Neovim<IoWrite>)
+        (val, nvim): (Value, &N)
     ) -> Result<Self, Self::Error> {
         let tup = val
             .as_array()
             .ok_or("Response is not an array")?;
         let buf_val = tup
-            .get(0)
+            .first()
             .ok_or("No buf handle")?;
         let pty_val = tup
             .get(1)
+            .filter(|pty| !pty.is_nil())
             .ok_or("No pty handle")?
             .as_str()
             .ok_or("PTY not a string")?;
+        if pty_val.is_empty() {
+            return Err(String::from("Empty PTY path"))
+        }
+
+        let buf = nvim.buffer(buf_val.clone());
+        let sink = SinkTarget::parse(pty_val);
+
+        Ok(OutputBuffer { buf, sink })
+    }
+}
 
-        let buf = Buffer::new(buf_val.clone(), nvim.clone());
-        let pty_path = PathBuf::from(pty_val);
 
-        Ok(OutputBuffer { buf, pty_path })
+/// Returns command that opens file in a way selected by --open-files
+fn files_open_command(mode: crate::cli::FilesOpenMode) -> &'static str {
+    use crate::cli::FilesOpenMode;
+    match mode {
+        FilesOpenMode::Buffers => "e",
+        FilesOpenMode::Arglist => "$argedit",
+        FilesOpenMode::Tabs => "tabedit",
+        FilesOpenMode::Splits => "vsplit",
     }
 }
 
 
+/// Maximum of 'scrollback' option supported by neovim
+const MAX_SCROLLBACK: u64 = 100_000;
+
+
 /// This struct provides commands that
 /// would be run on output buffer after creation
+#[derive(Clone)]
 pub struct OutputCommands {
+    context: String,
     edit: String,
     ft: String,
     notify_closed: String,
@@ -681,26 +2150,31 @@ pub struct OutputCommands {
     cmd_provided_by_user: String,
     lua_provided_by_user: String,
     after: String,
+    background_buf_nr: Option<i64>,
+    scrollback: String,
 }
 
 impl OutputCommands {
     fn create_with(
+        page_id: u128,
         cmd_provided_by_user: &str,
         lua_provided_by_user: &str,
         writeable: bool,
+        modifiable: bool,
     ) -> OutputCommands {
         let mut cmd_provided_by_user = String::from(cmd_provided_by_user);
         if !cmd_provided_by_user.is_empty() {
-            cmd_provided_by_user = format!("vim.cmd [====[{cmd_provided_by_user}]====]");
+            cmd_provided_by_user = format!("vim.cmd({})", lua_string(&cmd_provided_by_user));
         }
 
         let lua_provided_by_user = String::from(lua_provided_by_user);
 
         let mut edit = String::new();
+        if !writeable && !modifiable {
+            edit += "vim.bo.modifiable = false\n";
+        }
         if !writeable {
             let cmd = indoc! {r#"
-                vim.bo.modifiable = false
-
                 local function page_echo_notification(message)
                     vim.defer_fn(function()
                         local msg = '-- [PAGE] ' .. message .. ' --'
@@ -804,7 +2278,16 @@ impl OutputCommands {
             edit += cmd;
         }
 
+        // Page's own command line, to let autocommands and
+        // statusline plugins tell outputs apart
+        let page_command = lua_string(&shell_words::join(std::env::args()));
+        let context = formatdoc! {"
+            vim.b.page_id = '{page_id}'
+            vim.b.page_command = {page_command}
+        "};
+
         OutputCommands {
+            context,
             ft: String::new(),
             pre: String::new(),
             after: String::new(),
@@ -812,19 +2295,31 @@ impl OutputCommands {
             edit,
             cmd_provided_by_user,
             lua_provided_by_user,
+            background_buf_nr: None,
+            scrollback: format!("vim.bo.scrollback = {MAX_SCROLLBACK}"),
         }
     }
 
 
+    /// Prepares buffer with provided number without focusing on it
+    pub fn in_background(&mut self, buf_nr: i64) {
+        self.background_buf_nr = Some(buf_nr);
+    }
+
+
     pub fn for_file_buffer(
+        page_id: u128,
         cmd_provided_by_user: &str,
         lua_provided_by_user: &str,
-        writeable: bool
+        writeable: bool,
+        modifiable: bool
     ) -> OutputCommands {
         let mut cmds = Self::create_with(
+            page_id,
             cmd_provided_by_user,
             lua_provided_by_user,
-            writeable
+            writeable,
+            modifiable
         );
 
         let cmd = indoc! {"
@@ -842,6 +2337,7 @@ impl OutputCommands {
         page_id: u128,
         channel: u128,
         query_lines_count: usize,
+        follow: bool,
         opt: &crate::cli::OutputOptions
     ) -> OutputCommands {
         let cmd_provided_by_user = opt.command
@@ -852,25 +2348,148 @@ impl OutputCommands {
             .unwrap_or_default();
 
         let mut cmds = Self::create_with(
+            page_id,
             cmd_provided_by_user,
             lua_provided_by_user,
-            opt.writable
+            opt.writable,
+            opt.modifiable
         );
 
         let ft = &opt.filetype;
-        cmds.ft = format!("vim.bo.filetype = '{ft}'");
+        if !ft.is_empty() {
+            cmds.ft = format!("vim.bo.filetype = {}", lua_string(ft));
+        }
+
+        let scrollback = match opt.scrollback {
+            Some(0) | None => MAX_SCROLLBACK,
+            Some(lines) => lines.min(MAX_SCROLLBACK),
+        };
+        cmds.scrollback = format!("vim.bo.scrollback = {scrollback}");
+
+        if !cmds.lua_provided_by_user.is_empty() {
+            let lua_provided_by_user = cmds.lua_provided_by_user;
+            cmds.lua_provided_by_user = formatdoc! {"
+                ;(function(...)
+                    {lua_provided_by_user}
+                end)({{ page_id = '{page_id}', buf = vim.api.nvim_get_current_buf() }})
+            "};
+        }
 
+        // Unlisted buffer is wiped without BufDelete
+        let closed_events = if opt.ephemeral {
+            "{ 'BufDelete', 'BufWipeout' }"
+        } else {
+            "'BufDelete'"
+        };
         cmds.notify_closed = formatdoc! {r#"
-            vim.api.nvim_create_autocmd('BufDelete', {{
+            vim.b.page_channel = {channel}
+            vim.api.nvim_create_autocmd({closed_events}, {{
                 buffer = 0,
-                callback = function()
+                callback = function(ev)
                     pcall(function()
-                        vim.rpcnotify({channel}, 'page_buffer_closed', '{page_id}')
+                        vim.rpcnotify(vim.b[ev.buf].page_channel, 'page_buffer_closed', '{page_id}')
                     end)
                 end
             }})
         "#};
 
+        if opt.ephemeral {
+            cmds.edit += indoc! {"
+                vim.bo.readonly = true
+                vim.bo.buflisted = false
+                vim.bo.bufhidden = 'wipe'
+                vim.api.nvim_create_autocmd('BufLeave', {
+                    buffer = 0,
+                    callback = function(ev)
+                        vim.schedule(function()
+                            if vim.api.nvim_buf_is_valid(ev.buf) and vim.api.nvim_get_current_buf() ~= ev.buf then
+                                vim.api.nvim_buf_delete(ev.buf, { force = true })
+                            end
+                        end)
+                    end
+                })
+            "};
+        }
+
+        if opt.back_on_close {
+            cmds.pre += indoc! {"
+                local page_alternate_winid = vim.b.page_alternate_winid
+                vim.api.nvim_create_autocmd({ 'BufDelete', 'BufWipeout' }, {
+                    buffer = 0,
+                    once = true,
+                    callback = function()
+                        vim.schedule(function()
+                            if vim.api.nvim_win_is_valid(page_alternate_winid) then
+                                vim.api.nvim_set_current_win(page_alternate_winid)
+                            end
+                        end)
+                    end
+                })
+            "};
+        }
+
+        if opt.autoscroll {
+            cmds.pre += indoc! {"
+                local page_autoscroll_lines = vim.api.nvim_buf_line_count(0)
+                vim.api.nvim_buf_attach(0, false, {
+                    on_lines = function(_, buf)
+                        vim.schedule(function()
+                            if not vim.api.nvim_buf_is_valid(buf) then
+                                return
+                            end
+                            local lines = vim.api.nvim_buf_line_count(buf)
+                            for _, win in ipairs(vim.fn.win_findbuf(buf)) do
+                                -- Scrolled up windows are left as they are
+                                if vim.fn.line('w$', win) >= page_autoscroll_lines then
+                                    vim.api.nvim_win_call(win, function()
+                                        local height = vim.api.nvim_win_get_height(win)
+                                        vim.fn.winrestview {
+                                            topline = math.max(1, lines - height + 1)
+                                        }
+                                    end)
+                                end
+                            end
+                            page_autoscroll_lines = lines
+                        end)
+                    end
+                })
+            "};
+        }
+
+        if opt.pin_view {
+            cmds.pre += indoc! {"
+                local page_pinned_views = {}
+                vim.api.nvim_create_autocmd({ 'CursorMoved', 'WinScrolled' }, {
+                    buffer = 0,
+                    callback = function()
+                        local win = vim.api.nvim_get_current_win()
+                        if vim.fn.line('.') >= vim.api.nvim_buf_line_count(0) then
+                            page_pinned_views[win] = nil
+                        else
+                            page_pinned_views[win] = vim.fn.winsaveview()
+                        end
+                    end
+                })
+                vim.api.nvim_buf_attach(0, false, {
+                    on_lines = function(_, buf)
+                        vim.schedule(function()
+                            for win, view in pairs(page_pinned_views) do
+                                if vim.api.nvim_win_is_valid(win)
+                                    and vim.api.nvim_win_get_buf(win) == buf
+                                then
+                                    vim.api.nvim_win_call(win, function()
+                                        vim.fn.winrestview(view)
+                                    end)
+                                else
+                                    page_pinned_views[win] = nil
+                                end
+                            end
+                        end)
+                    end
+                })
+            "};
+        }
+
         if query_lines_count != 0 {
 
             let prefix = cmds.pre;
@@ -879,7 +2498,7 @@ impl OutputCommands {
                 vim.b.page_query_size = {query_lines_count}
                 local function fetch_lines(opt)
                     local ok = pcall(function()
-                        vim.rpcnotify({channel}, 'page_fetch_lines', '{page_id}', opt.args)
+                        vim.rpcnotify(vim.b.page_channel, 'page_fetch_lines', '{page_id}', opt.args)
                     end)
                     if not ok then
                         page_echo_notification 'closed'
@@ -888,6 +2507,9 @@ impl OutputCommands {
                 local function define_query_cmd()
                     local cmd_opts = {{ force = true, nargs = '?' }}
                     vim.api.nvim_create_user_command('Page', fetch_lines, cmd_opts)
+                    vim.api.nvim_create_user_command('PageAll', function()
+                        fetch_lines {{ args = -1 }}
+                    end, {{ force = true }})
                 end
                 define_query_cmd()
                 vim.api.nvim_create_autocmd('BufEnter', {{
@@ -911,22 +2533,55 @@ impl OutputCommands {
             }
         }
 
-        if opt.pwd {
-            let pwd = std::env::var("PWD")
-                .unwrap();
+        if opt.winbar {
+            let follow = u8::from(follow);
+            let prefix = cmds.pre;
+            cmds.pre = formatdoc! {r#"
+                {prefix}
+                vim.b.page_lines_read = 0
+                vim.b.page_follow = {follow}
+                pcall(function()
+                    vim.wo.winbar = "%t | %{{get(b:, 'page_lines_read', 0)}} lines read"
+                        .. "%{{get(b:, 'page_follow', 0) ? ' | following' : ''}}"
+                end)
+            "#};
+        }
+
+        let current_dir = || std::env::current_dir()
+            .map_err(|e| log::error!(target: "cwd", "Cannot get current directory: {e}"))
+            .ok();
+
+        let cwd = match &opt.cwd {
+            // Neovim might be running in other directory
+            Some(cwd) if std::path::Path::new(cwd).is_relative() => current_dir()
+                .map(|d| d.join(cwd)),
+            Some(cwd) => Some(PathBuf::from(cwd)),
+            None if opt.pwd => current_dir(),
+            None => None,
+        };
+
+        if let Some(cwd) = cwd {
+            let cwd = cwd.to_string_lossy();
+            log::trace!(target: "cwd", "{cwd}");
 
+            // Path is escaped by fnameescape() for :lcd, so spaces survive
+            let cwd = lua_string(&cwd);
             let prefix = cmds.pre;
             cmds.pre = formatdoc! {r#"
                 {prefix}
-                vim.b.page_lcd_backup = getcwd()
-                vim.cmd 'lcd {pwd}'
+                vim.b.page_lcd_backup = vim.fn.getcwd()
+                local page_cwd = {cwd}
+                local function page_lcd(dir)
+                    vim.cmd('lcd ' .. vim.fn.fnameescape(dir))
+                end
+                page_lcd(page_cwd)
                 vim.api.nvim_create_autocmd('BufEnter', {{
                     buffer = 0,
-                    command = 'lcd {pwd}'
+                    callback = function() page_lcd(page_cwd) end
                 }})
                 vim.api.nvim_create_autocmd('BufLeave', {{
                     buffer = 0,
-                    command = 'exe "lcd" . b:page_lcd_backup'
+                    callback = function() page_lcd(vim.b.page_lcd_backup) end
                 }})
             "#};
         }
@@ -934,3 +2589,639 @@ impl OutputCommands {
         cmds
     }
 }
+
+
+/// Converts value of --float-width, --float-height, --float-row
+/// or --float-col into lua expression, percents are resolved
+/// against editor dimension available in `dimension` variable
+fn float_size_to_lua(size: crate::cli::FloatSize, dimension: &str) -> String {
+    use crate::cli::FloatSize;
+
+    match size {
+        FloatSize::Cells(cells) => cells.to_string(),
+        FloatSize::Percent(percent) => format!("math.floor({dimension} * {percent} / 100)"),
+    }
+}
+
+
+/// Converts value of --float-border into
+/// `border` field of `nvim_open_win` config
+fn float_border_to_lua(border: &crate::cli::FloatBorder) -> String {
+    use crate::cli::FloatBorder;
+
+    match border {
+        FloatBorder::Style(style) => lua_string(style),
+        FloatBorder::Chars(chars) => {
+            let chars = chars
+                .iter()
+                .map(|c| lua_string(c))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!("{{ {chars} }}")
+        }
+    }
+}
+
+
+/// Quotes string as lua literal, so that user provided values (paths,
+/// titles, instance names, commands) can't break generated lua code
+fn lua_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '\'' => quoted.push_str("\\'"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\000"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Call received by `MockNeovim`, buffers and windows are identified
+    /// by their handles
+    #[derive(Debug, Clone, PartialEq)]
+    enum Call {
+        ExecLua(String, Vec<Value>),
+        Command(String),
+        Eval(String),
+        Function(String, Vec<Value>),
+        GetVar(String),
+        GetCurrentBuf,
+        SetCurrentBuf(Value),
+        SetCurrentWin(Value),
+        ChanSend(i64, String),
+        BufGetNumber(Value),
+        BufSetVar(Value, String, Value),
+        BufSetOption(Value, String, Value),
+        WinGetNumber(Value),
+    }
+
+    type Respond = dyn Fn(&Call) -> Result<Value, Box<CallError>> + Send + Sync;
+
+    /// Records calls made by `Actions` and answers them with `respond`
+    #[derive(Clone)]
+    struct MockNeovim {
+        calls: Arc<Mutex<Vec<Call>>>,
+        respond: Arc<Respond>,
+    }
+
+    impl MockNeovim {
+        fn new(respond: impl Fn(&Call) -> Result<Value, Box<CallError>> + Send + Sync + 'static) -> MockNeovim {
+            MockNeovim {
+                calls: Arc::default(),
+                respond: Arc::new(respond),
+            }
+        }
+
+        fn calls(&self) -> Vec<Call> {
+            self.calls
+                .lock()
+                .unwrap()
+                .clone()
+        }
+
+        fn call(&self, call: Call) -> Result<Value, Box<CallError>> {
+            let response = (self.respond)(&call);
+            self.calls
+                .lock()
+                .unwrap()
+                .push(call);
+
+            response
+        }
+    }
+
+    #[derive(Clone)]
+    struct MockHandle {
+        handle: Value,
+        nvim: MockNeovim,
+    }
+
+    #[async_trait::async_trait]
+    impl NeovimApi for MockNeovim {
+        type Buffer = MockHandle;
+        type Window = MockHandle;
+
+        fn buffer(&self, handle: Value) -> Self::Buffer {
+            MockHandle { handle, nvim: self.clone() }
+        }
+
+        async fn exec_lua(&self, code: &str, args: Vec<Value>) -> Result<Value, Box<CallError>> {
+            self.call(Call::ExecLua(String::from(code), args))
+        }
+
+        async fn command(&self, command: &str) -> Result<(), Box<CallError>> {
+            self.call(Call::Command(String::from(command))).map(drop)
+        }
+
+        async fn eval(&self, expr: &str) -> Result<Value, Box<CallError>> {
+            self.call(Call::Eval(String::from(expr)))
+        }
+
+        async fn call_function(&self, fname: &str, args: Vec<Value>) -> Result<Value, Box<CallError>> {
+            self.call(Call::Function(String::from(fname), args))
+        }
+
+        async fn get_var(&self, name: &str) -> Result<Value, Box<CallError>> {
+            self.call(Call::GetVar(String::from(name)))
+        }
+
+        async fn get_current_buf(&self) -> Result<Self::Buffer, Box<CallError>> {
+            self.call(Call::GetCurrentBuf)
+                .map(|handle| self.buffer(handle))
+        }
+
+        async fn set_current_buf(&self, buffer: &Self::Buffer) -> Result<(), Box<CallError>> {
+            self.call(Call::SetCurrentBuf(buffer.handle.clone())).map(drop)
+        }
+
+        async fn set_current_win(&self, window: &Self::Window) -> Result<(), Box<CallError>> {
+            self.call(Call::SetCurrentWin(window.handle.clone())).map(drop)
+        }
+
+        async fn chan_send(&self, chan: i64, data: &str) -> Result<(), Box<CallError>> {
+            self.call(Call::ChanSend(chan, String::from(data))).map(drop)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl NeovimBufferApi for MockHandle {
+        fn get_value(&self) -> &Value {
+            &self.handle
+        }
+
+        async fn get_number(&self) -> Result<i64, Box<CallError>> {
+            self.nvim
+                .call(Call::BufGetNumber(self.handle.clone()))
+                .map(|_| self.handle.as_i64().unwrap_or_default())
+        }
+
+        async fn set_var(&self, name: &str, value: Value) -> Result<(), Box<CallError>> {
+            self.nvim.call(Call::BufSetVar(self.handle.clone(), String::from(name), value)).map(drop)
+        }
+
+        async fn set_option(&self, name: &str, value: Value) -> Result<(), Box<CallError>> {
+            self.nvim.call(Call::BufSetOption(self.handle.clone(), String::from(name), value)).map(drop)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl NeovimWindowApi for MockHandle {
+        async fn get_number(&self) -> Result<i64, Box<CallError>> {
+            self.nvim
+                .call(Call::WinGetNumber(self.handle.clone()))
+                .map(|_| self.handle.as_i64().unwrap_or_default())
+        }
+    }
+
+    fn neovim_error(message: &str) -> Box<CallError> {
+        Box::new(CallError::NeovimError(Some(0), String::from(message)))
+    }
+
+    fn lua_calls(calls: &[Call]) -> Vec<&str> {
+        calls
+            .iter()
+            .filter_map(|call| match call {
+                Call::ExecLua(code, _) => Some(code.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+
+    /// Collects log records, so that tests might check
+    /// what was logged and how many times
+    struct CapturingLogger;
+
+    static LOGGED: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGGED
+                .lock()
+                .unwrap()
+                .push((String::from(record.target()), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CapturingLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    fn logged(target: &str, containing: &str) -> usize {
+        LOGGED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(t, m)| t == target && m.contains(containing))
+            .count()
+    }
+
+
+    #[tokio::test]
+    async fn split_output_buffer_lua() {
+        use clap::Parser;
+        let opt = crate::cli::Options::try_parse_from(["page", "-r"]).unwrap();
+
+        let nvim = MockNeovim::new(|_| Ok(Value::from(vec![Value::from(3), Value::from("/dev/pts/7")])));
+        let mut actions = Actions::from(nvim.clone());
+
+        let outp = actions
+            .try_create_split_output_buffer(&opt.output.split, false, crate::cli::OutputBackend::Pty)
+            .await
+            .unwrap();
+        assert_eq!(outp.buf.get_value(), &Value::from(3));
+        assert_eq!(outp.sink, SinkTarget::Pty(PathBuf::from("/dev/pts/7")));
+
+        let split_cmd = split_window_cmd(&opt.output.split).unwrap();
+        assert!(split_cmd.contains("vim.cmd('belowright ' .. tostring(math.floor(((w / 2) * 3) / 2)) .. 'vsplit')"), "{split_cmd}");
+        assert!(split_cmd.contains("vim.api.nvim_win_set_option(win, 'winfixwidth', true)"), "{split_cmd}");
+
+        let calls = nvim.calls();
+        let [code] = lua_calls(&calls)[..] else {
+            panic!("{calls:?}")
+        };
+        assert!(code.contains(&format!("{split_cmd}\nlocal buf = vim.api.nvim_create_buf(true, false)")), "{code}");
+        assert!(code.trim_end().ends_with("return { buf, pty }"), "{code}");
+    }
+
+    #[tokio::test]
+    async fn files_are_opened_in_sized_splits_despite_errors() {
+        use clap::Parser;
+        let opt = crate::cli::Options::try_parse_from(["page", "--open-files=splits", "-r", "bad.rs", "b.rs"]).unwrap();
+
+        let nvim = MockNeovim::new(|call| match call {
+            Call::Command(cmd) if cmd.contains("bad.rs") => Err(neovim_error("E325: ATTENTION")),
+            _ => Ok(Value::Nil),
+        });
+        let mut actions = Actions::from(nvim.clone());
+
+        let cmds = OutputCommands::for_file_buffer(1, "", "", false, false);
+        let failed = actions
+            .open_file_buffers(&opt.files, opt.open_files, &opt.output.split, (1, 1000), &cmds, false)
+            .await;
+        let failed = failed
+            .iter()
+            .map(|(f, _)| f.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["bad.rs"]);
+
+        let split_cmd = split_window_cmd(&opt.output.split).unwrap();
+        let calls = nvim.calls();
+        assert_eq!(calls[..5], [
+            Call::ExecLua(split_cmd.clone(), vec![]),
+            Call::Command(String::from("e bad.rs")),
+            Call::Command(String::from("close")),
+            Call::ExecLua(split_cmd, vec![]),
+            Call::Command(String::from("e b.rs")),
+        ]);
+
+        let [prepare, scroll] = lua_calls(&calls[5..])[..] else {
+            panic!("{calls:?}")
+        };
+        assert!(prepare.contains("PageOpenFile"), "{prepare}");
+        assert!(scroll.contains("ggM"), "{scroll}");
+    }
+
+    #[tokio::test]
+    async fn floating_window_is_fitted_with_single_request() {
+        let nvim = MockNeovim::new(|_| Ok(Value::from(false)));
+        let mut actions = Actions::from(nvim.clone());
+        let buf = nvim.buffer(Value::from(4));
+
+        assert!(!actions.fit_floating_window_to_content(&buf, 12, None).await);
+        assert!(!actions.fit_floating_window_to_content(&buf, 20, Some(15)).await);
+
+        let calls = nvim.calls();
+        let [Call::ExecLua(_, unlimited), Call::ExecLua(_, limited)] = calls.as_slice() else {
+            panic!("{calls:?}")
+        };
+        assert_eq!(unlimited, &[Value::from(4), Value::from(12), Value::Nil]);
+        assert_eq!(limited, &[Value::from(4), Value::from(20), Value::from(15)]);
+    }
+
+    #[tokio::test]
+    async fn output_buffer_creation_error_is_returned() {
+        use clap::Parser;
+        let opt = crate::cli::Options::try_parse_from(["page", "--tab"]).unwrap();
+
+        let nvim = MockNeovim::new(|_| Err(neovim_error("E36: Not enough room")));
+        let mut actions = Actions::from(nvim);
+
+        let created = actions
+            .try_create_split_output_buffer(&opt.output.split, false, crate::cli::OutputBackend::Buffer)
+            .await;
+        assert!(matches!(created, Err(Error::Call(_))), "{:?}", created.err());
+    }
+
+    #[tokio::test]
+    async fn prepare_output_buffer_lua() {
+        let nvim = MockNeovim::new(|_| Ok(Value::Nil));
+        let mut actions = Actions::from(nvim.clone());
+
+        let cmds = output_commands(&["-t", "markdown", "--scrollback", "500"]);
+        actions
+            .prepare_output_buffer(4, 1000, cmds)
+            .await;
+
+        let calls = nvim.calls();
+        let [code] = lua_calls(&calls)[..] else {
+            panic!("{calls:?}")
+        };
+        assert!(code.starts_with("vim.b.page_alternate_bufnr = 4\nvim.b.page_alternate_winid = 1000\n"), "{code}");
+
+        let expected_order = [
+            "vim.bo.scrollback = 500\n",
+            "vim.wo.scrolloff, vim.wo.signcolumn, vim.wo.number = 999, 'no', false\n",
+            "vim.bo.filetype = 'markdown'\n",
+            "pattern = 'PageOpen'",
+            "vim.cmd 'redraw'",
+        ];
+        let positions = expected_order
+            .iter()
+            .map(|line| code.find(line).unwrap_or_else(|| panic!("{line} in {code}")))
+            .collect::<Vec<_>>();
+        assert!(positions.windows(2).all(|p| p[0] < p[1]), "{code}");
+    }
+
+    #[tokio::test]
+    async fn unchanged_title_is_not_sent() {
+        let nvim = MockNeovim::new(|_| Ok(Value::Nil));
+        let mut actions = Actions::from(nvim.clone());
+        let buf = nvim.buffer(Value::from(1));
+        let other_buf = nvim.buffer(Value::from(2));
+
+        actions.update_buffer_title(&buf, "title").await;
+        actions.update_buffer_title(&buf, "title").await;
+        assert_eq!(nvim.calls().len(), 1);
+
+        actions.update_buffer_title(&buf, "other title").await;
+        actions.update_buffer_title(&other_buf, "other title").await;
+        assert_eq!(nvim.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_title_update_is_logged_once() {
+        capture_logs();
+
+        let nvim = MockNeovim::new(|_| Err(neovim_error("Failed to rename buffer to failing title")));
+        let mut actions = Actions::from(nvim.clone());
+        let buf = nvim.buffer(Value::from(1));
+
+        actions.update_buffer_title(&buf, "title").await;
+
+        assert_eq!(nvim.calls().len(), 1);
+        assert_eq!(logged("update title", "Failed to rename buffer to failing title"), 1);
+    }
+
+    #[tokio::test]
+    async fn instance_is_found_when_other_buffer_errors() {
+        let nvim = MockNeovim::new(|call| match call {
+            // Lua reads instance mark of each buffer in pcall, so errors
+            // of other buffers are skipped and the scan finds target
+            Call::ExecLua(code, _) if code.contains("local ok = pcall(function()") => {
+                Ok(Value::from(vec![Value::from(5), Value::from("buffer:5")]))
+            }
+            _ => Err(neovim_error("unexpected call")),
+        });
+        let mut actions = Actions::from(nvim.clone());
+
+        let outp = actions
+            .find_instance_buffer("page_instance", "target")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outp.buf.get_value(), &Value::from(5));
+        assert_eq!(outp.sink, SinkTarget::Buffer(5));
+
+        let calls = nvim.calls();
+        let [code] = lua_calls(&calls)[..] else {
+            panic!("{calls:?}")
+        };
+        assert!(code.contains("vim.api.nvim_buf_get_var(buf, 'page_instance')"), "{code}");
+        assert!(code.contains("if ok and inst_name == 'target' then"), "{code}");
+    }
+
+    #[tokio::test]
+    async fn instance_scan_error_is_returned() {
+        let nvim = MockNeovim::new(|_| Err(neovim_error("channel closed")));
+        let mut actions = Actions::from(nvim);
+
+        assert!(actions.find_instance_buffer("page_instance", "target").await.is_err());
+    }
+
+    #[test]
+    fn output_buffer_without_pty() {
+        let nvim = MockNeovim::new(|_| Ok(Value::Nil));
+        let convert = |response: Vec<Value>| OutputBuffer::try_from((Value::from(response), &nvim)).err();
+
+        // Map returned by nvim_get_chan_info() has no `pty` key
+        let chan_info = Value::Map(vec![(Value::from("id"), Value::from(3)), (Value::from("mode"), Value::from("terminal"))]);
+
+        assert_eq!(convert(vec![Value::from(1)]).as_deref(), Some("No pty handle"));
+        assert_eq!(convert(vec![Value::from(1), Value::Nil]).as_deref(), Some("No pty handle"));
+        assert_eq!(convert(vec![Value::from(1), chan_info]).as_deref(), Some("PTY not a string"));
+        assert_eq!(convert(vec![Value::from(1), Value::from("")]).as_deref(), Some("Empty PTY path"));
+        assert_eq!(convert(vec![Value::from(1), Value::from("/dev/pts/2")]), None);
+    }
+
+    #[tokio::test]
+    async fn missing_pty_error_is_returned() {
+        use clap::Parser;
+        let opt = crate::cli::Options::try_parse_from(["page", "--tab"]).unwrap();
+
+        let nvim = MockNeovim::new(|_| Err(neovim_error("No PTY on terminal channel 3, output buffer is not connected to PTY device")));
+        let mut actions = Actions::from(nvim.clone());
+
+        let created = actions
+            .try_create_split_output_buffer(&opt.output.split, false, crate::cli::OutputBackend::Pty)
+            .await;
+        assert!(matches!(created, Err(Error::Call(_))), "{:?}", created.err());
+
+        let calls = nvim.calls();
+        let [code] = lua_calls(&calls)[..] else {
+            panic!("{calls:?}")
+        };
+        assert!(code.contains("local pty = chan_info.pty\nif pty == nil or pty == '' then"), "{code}");
+    }
+
+    #[test]
+    fn key_not_found_messages() {
+        assert!(is_key_not_found(&CallError::NeovimError(Some(0), String::from("Key not found: page_instance")), "page_instance"));
+        assert!(is_key_not_found(&CallError::NeovimError(Some(0), String::from("Vim:E121: Key 'page_instance' not found")), "page_instance"));
+
+        assert!(!is_key_not_found(&CallError::NeovimError(Some(0), String::from("Key not found: other")), "page_instance"));
+        assert!(!is_key_not_found(&CallError::NeovimError(Some(0), String::from("Key not found: page_instance_x")), "page_instance"));
+        assert!(!is_key_not_found(&CallError::NeovimError(Some(0), String::from("Invalid buffer id: 7")), "page_instance"));
+        assert!(!is_key_not_found(&CallError::WrongValueType(Value::Nil), "page_instance"));
+    }
+
+    #[test]
+    fn lua_string_escapes_quotes_and_backslashes() {
+        assert_eq!(lua_string("it's"), r"'it\'s'");
+        assert_eq!(lua_string(r"C:\dir\"), r"'C:\\dir\\'");
+        assert_eq!(lua_string(r"\'"), r"'\\\''");
+        assert_eq!(lua_string(r#"say "hi""#), r#"'say "hi"'"#);
+    }
+
+    #[test]
+    fn lua_string_escapes_line_breaks() {
+        assert_eq!(lua_string("a\nb\r\nc"), r"'a\nb\r\nc'");
+    }
+
+    #[test]
+    fn lua_string_escapes_nul_before_digit() {
+        // \0 followed by digit would be read as different decimal escape
+        assert_eq!(lua_string("\u{0}1"), r"'\0001'");
+        assert_eq!(lua_string("a\u{0}"), r"'a\000'");
+    }
+
+    fn output_commands(args: &[&str]) -> OutputCommands {
+        use clap::Parser;
+        let opt = crate::cli::Options::try_parse_from(std::iter::once("page").chain(args.iter().copied()))
+            .unwrap();
+
+        OutputCommands::for_output_buffer(1, 2, 0, false, &opt.output)
+    }
+
+    #[test]
+    fn filetype_fragment() {
+        assert_eq!(output_commands(&[]).ft, "vim.bo.filetype = 'pager'");
+        assert_eq!(output_commands(&["-t", "markdown.pandoc"]).ft, "vim.bo.filetype = 'markdown.pandoc'");
+
+        // Empty filetype leaves it to be detected by neovim
+        assert_eq!(output_commands(&["-t", ""]).ft, "");
+    }
+
+    #[test]
+    fn scrollback_option() {
+        assert_eq!(output_commands(&[]).scrollback, "vim.bo.scrollback = 100000");
+        assert_eq!(output_commands(&["--scrollback", "0"]).scrollback, "vim.bo.scrollback = 100000");
+        assert_eq!(output_commands(&["--scrollback", "500"]).scrollback, "vim.bo.scrollback = 500");
+        assert_eq!(output_commands(&["--scrollback", "1000000"]).scrollback, "vim.bo.scrollback = 100000");
+    }
+
+    #[test]
+    fn cwd_with_space() {
+        let pre = output_commands(&["--cwd", "/tmp/my dir"]).pre;
+
+        assert!(pre.contains("local page_cwd = '/tmp/my dir'"), "{pre}");
+        assert!(pre.contains("vim.fn.fnameescape(dir)"), "{pre}");
+    }
+
+    #[test]
+    fn relative_cwd_is_resolved_by_page() {
+        let pre = output_commands(&["--cwd", "sub dir"]).pre;
+        let resolved = std::env::current_dir()
+            .unwrap()
+            .join("sub dir");
+
+        assert!(pre.contains(&format!("local page_cwd = {}", lua_string(&resolved.to_string_lossy()))), "{pre}");
+    }
+
+    #[test]
+    fn joined_buffer_lines_rebase_highlights() {
+        let mut joined = BufferLines {
+            lines: vec!["a".to_string(), "b".to_string()],
+            highlights: vec![(1, "PageAnsiRed", 0, 1)],
+            first: true,
+        };
+        joined.join(BufferLines {
+            lines: vec!["c".to_string()],
+            highlights: vec![(0, "PageAnsiBold", 0, 1)],
+            first: false,
+        });
+
+        assert!(joined.first);
+        assert_eq!(joined.lines, ["a", "b", "c"]);
+        assert_eq!(joined.highlights, [(1, "PageAnsiRed", 0, 1), (2, "PageAnsiBold", 0, 1)]);
+    }
+
+    fn connection_lost() -> Box<CallError> {
+        let (tx, rx) = futures::channel::oneshot::channel::<()>();
+        drop(tx);
+        let canceled = futures::executor::block_on(rx).unwrap_err();
+
+        Box::new(CallError::InternalReceiveError(canceled, String::from("nvim_exec_lua")))
+    }
+
+    fn reconnect_to(nvim: &MockNeovim, attempts: &Arc<Mutex<usize>>) -> ReconnectFn<MockNeovim> {
+        let (nvim, attempts) = (nvim.clone(), attempts.clone());
+
+        Arc::new(move || {
+            *attempts.lock().unwrap() += 1;
+            let nvim = nvim.clone();
+            Box::pin(async move { Some(nvim) })
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn buffer_sink_resends_lines_after_reconnect() {
+        let lost = MockNeovim::new(|_| Err(connection_lost()));
+        let reconnected = MockNeovim::new(|_| Ok(Value::Nil));
+        let attempts = Arc::new(Mutex::new(0));
+
+        let mut sink = BufferSink::open(lost.clone(), 5, true, false, false, Some(reconnect_to(&reconnected, &attempts)));
+        std::io::Write::write_all(&mut sink, b"one\ntwo\n").unwrap();
+        drop(sink);
+
+        assert_eq!(*attempts.lock().unwrap(), 1);
+        assert_eq!(lost.calls().len(), 1);
+
+        let calls = reconnected.calls();
+        let [Call::ExecLua(_, args)] = calls.as_slice() else {
+            panic!("{calls:?}")
+        };
+        assert_eq!(args[1], Value::from(vec![Value::from("one"), Value::from("two")]));
+        // Retried lines are still the first ones written
+        assert_eq!(args[3], Value::from(true));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn channel_sink_doesnt_reconnect_on_neovim_error() {
+        let nvim = MockNeovim::new(|_| Err(neovim_error("Invalid channel")));
+        let attempts = Arc::new(Mutex::new(0));
+
+        let mut sink = ChannelSink::open(nvim.clone(), 3, Some(reconnect_to(&nvim, &attempts)));
+        std::io::Write::write_all(&mut sink, b"text\n").unwrap();
+        drop(sink);
+
+        assert_eq!(*attempts.lock().unwrap(), 0);
+        assert_eq!(nvim.calls(), [Call::ChanSend(3, String::from("text\r\n"))]);
+    }
+
+    #[test]
+    fn sink_target_round_trips() {
+        for (s, sink) in [
+            ("/dev/pts/3", SinkTarget::Pty(PathBuf::from("/dev/pts/3"))),
+            ("channel:7", SinkTarget::Channel(7)),
+            ("buffer:12", SinkTarget::Buffer(12)),
+            ("buffer:x", SinkTarget::Pty(PathBuf::from("buffer:x"))),
+        ] {
+            assert_eq!(SinkTarget::parse(s), sink);
+            assert_eq!(sink.to_string(), s);
+        }
+    }
+}
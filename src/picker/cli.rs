@@ -76,6 +76,12 @@ pub struct Options {
     #[clap(short='a', env="NVIM")]
     pub address: Option<String>,
 
+    /// Token passed to `PageAuthenticate(token)` function defined
+    /// in host neovim config before nv issues any other request;
+    /// nv exits unless it returns true [ignored without <ADDRESS>]
+    #[clap(long="token", env="PAGE_TOKEN", hide_env_values=true)]
+    pub token: Option<String>,
+
     /// Arguments that will be passed to child neovim process
     /// spawned when <ADDRESS> is missing
     #[clap(short='A', env="NVIM_PAGE_PICKER_ARGS")]
@@ -70,14 +70,32 @@ async fn connect_neovim(env_ctx: context::Env) {
 
     connection::init_panic_hook();
 
+    let conn_opt = connection::ConnectionOptions {
+        address: &env_ctx.opt.address,
+        token: &env_ctx.opt.token,
+        nvim_bin: "nvim",
+        nvim_env: &[],
+        config: &env_ctx.opt.config,
+        custom_args: &env_ctx.opt.arguments,
+        socket: &None,
+        listen_addr_passing: connection::ListenAddressPassing::Argument,
+        connect_interval: std::time::Duration::from_millis(16),
+        connect_timeout: std::time::Duration::from_millis(4096),
+        ready_handshake: false,
+        notification_capacity: 16,
+        print_protection: false,
+        follow: false,
+        heartbeat_interval: None,
+        remove_tmp_dir: false,
+        reconnect: false,
+    };
+
     let mut nvim_conn: NeovimConnection = connection::open(
         &env_ctx.tmp_dir,
         env_ctx.page_id,
-        &env_ctx.opt.address,
-        &env_ctx.opt.config,
-        &env_ctx.opt.config,
-        false
-    ).await;
+        conn_opt
+    ).await
+    .unwrap_or_else(|e| connection::exit_with_error(&e));
 
     if let Some(cmd) = &env_ctx.opt.command_only {
         nvim_conn.nvim_actions